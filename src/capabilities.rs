@@ -0,0 +1,126 @@
+use crate::fs_library::{self, FileSystem};
+use crate::io_library;
+
+/// What a script may do beyond computing values. The built-ins of a capability that is not granted are not
+/// defined at all, so a script sees an unknown name and may even bind that name itself.
+///
+/// `standard()`, what `Eva::new` and `Interpreter::new` use, grants `io` only: reading and writing the streams the
+/// host hands in. `none()` grants nothing; the other capabilities are added one at a time.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Capabilities {
+    io: bool,
+    filesystem: Option<FileSystem>,
+    env_vars: bool,
+    clock: bool,
+    random: bool,
+    process: bool,
+}
+
+pub const ALLOW_FLAGS: &str = "[--allow-fs=<dir>] [--allow-env-vars] [--allow-clock] [--allow-random] [--allow-process] [--allow-all] [--deny-io]";
+
+impl Capabilities {
+    pub fn none() -> Capabilities {
+        Capabilities { io: false, filesystem: None, env_vars: false, clock: false, random: false, process: false }
+    }
+    pub fn standard() -> Capabilities {
+        Capabilities::none().io()
+    }
+    /// `print`, `println`, `eprint` and `read-line`.
+    pub fn io(self) -> Capabilities {
+        Capabilities { io: true, ..self }
+    }
+    /// The file built-ins, confined to the root of `filesystem`.
+    pub fn fs(self, filesystem: FileSystem) -> Capabilities {
+        Capabilities { filesystem: Some(filesystem), ..self }
+    }
+    /// `env-var`.
+    pub fn env_vars(self) -> Capabilities {
+        Capabilities { env_vars: true, ..self }
+    }
+    /// `now`.
+    pub fn clock(self) -> Capabilities {
+        Capabilities { clock: true, ..self }
+    }
+    /// `random`.
+    pub fn random(self) -> Capabilities {
+        Capabilities { random: true, ..self }
+    }
+    /// `process-run`.
+    pub fn process(self) -> Capabilities {
+        Capabilities { process: true, ..self }
+    }
+    pub fn filesystem(&self) -> Option<&FileSystem> {
+        self.filesystem.as_ref()
+    }
+    /// Whether `name` may be used as a built-in; names that need no capability are always allowed.
+    pub fn allows(&self, name: &str) -> bool {
+        match name {
+            name if io_library::is_io_builtin(name) => self.io,
+            name if fs_library::is_fs_builtin(name) => self.filesystem.is_some(),
+            "env-var" => self.env_vars,
+            "now" => self.clock,
+            "random" => self.random,
+            "process-run" => self.process,
+            _ => true,
+        }
+    }
+    /// Reads the `--allow-*` and `--deny-io` flags of the command line, starting from `standard()`; other
+    /// arguments are ignored.
+    pub fn from_flags(args: &[String]) -> Result<Capabilities, String> {
+        let mut capabilities = Capabilities::standard();
+        for arg in args.iter().filter(|arg| arg.starts_with("--allow-") || arg.starts_with("--deny-")) {
+            capabilities = match arg.as_str() {
+                "--allow-env-vars" => capabilities.env_vars(),
+                "--allow-clock" => capabilities.clock(),
+                "--allow-random" => capabilities.random(),
+                "--allow-process" => capabilities.process(),
+                "--allow-all" => capabilities.env_vars().clock().random().process(),
+                "--deny-io" => Capabilities { io: false, ..capabilities },
+                flag => match flag.strip_prefix("--allow-fs=") {
+                    Some(root) => capabilities.fs(FileSystem::new(root).map_err(|error| format!("{}: {}", root, error))?),
+                    None => return Err(format!("unknown flag `{}`", flag)),
+                },
+            };
+        }
+        Ok(capabilities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capabilities::Capabilities;
+    use crate::{EvalDataType, Interpreter, InterpreterError};
+
+    fn flags(args: &[&str]) -> Result<Capabilities, String> {
+        Capabilities::from_flags(&args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn test_missing_capabilities_are_undefined_names() {
+        let interpreter = Interpreter::new().with_capabilities(Capabilities::none());
+        for call in ["(println 1)", "(now)", "(random 6)", "(env-var \"HOME\")", "(process-run \"true\")", "(list-dir)"] {
+            assert!(matches!(interpreter.eval_str(call), Err(InterpreterError::Runtime(_))), "{}", call);
+        }
+        // Without the capability the name is free for the script to use.
+        assert_eq!(interpreter.eval_str("(def random (n) n) (random 6)"), Ok(EvalDataType::Number(6)));
+    }
+
+    #[test]
+    fn test_granted_capabilities() {
+        let interpreter = Interpreter::new().with_capabilities(Capabilities::none().clock().random().env_vars());
+        assert!(matches!(interpreter.eval_str("(now)"), Ok(EvalDataType::Number(millis)) if millis > 1_600_000_000_000));
+        assert!(matches!(interpreter.eval_str("(random 6)"), Ok(EvalDataType::Number(roll)) if roll < 6));
+        assert!(matches!(interpreter.eval_str("(env-var \"PATH\")"), Ok(EvalDataType::String(_))));
+        assert_eq!(interpreter.eval_str("(env-var \"EVA_SURELY_UNSET\")"), Ok(EvalDataType::Null));
+        assert!(interpreter.eval_str("(println 1)").is_err());
+    }
+
+    #[test]
+    fn test_flags() {
+        assert_eq!(flags(&["--no-optimize", "main.eva"]), Ok(Capabilities::standard()));
+        assert_eq!(flags(&["--allow-clock", "--deny-io"]), Ok(Capabilities::none().clock()));
+        assert_eq!(flags(&["--allow-all"]), Ok(Capabilities::standard().env_vars().clock().random().process()));
+        assert!(flags(&["--allow-fs=/nonexistent/eva"]).unwrap_err().starts_with("/nonexistent/eva: "));
+        assert_eq!(flags(&["--allow-network"]), Err("unknown flag `--allow-network`".to_owned()));
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::eval_error::EvalError;
+use crate::type_library::type_name;
+use crate::EvalDataType;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: EvalDataType,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {}, found {} {}", self.expected, type_name(&self.found), self.found)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl From<u128> for EvalDataType {
+    fn from(value: u128) -> EvalDataType {
+        EvalDataType::Number(value)
+    }
+}
+
+impl From<u64> for EvalDataType {
+    fn from(value: u64) -> EvalDataType {
+        EvalDataType::Number(value.into())
+    }
+}
+
+impl From<u32> for EvalDataType {
+    fn from(value: u32) -> EvalDataType {
+        EvalDataType::Number(value.into())
+    }
+}
+
+impl From<usize> for EvalDataType {
+    fn from(value: usize) -> EvalDataType {
+        EvalDataType::Number(value as u128)
+    }
+}
+
+impl From<bool> for EvalDataType {
+    fn from(value: bool) -> EvalDataType {
+        EvalDataType::Bool(value)
+    }
+}
+
+impl From<String> for EvalDataType {
+    fn from(value: String) -> EvalDataType {
+        EvalDataType::String(value)
+    }
+}
+
+impl From<&str> for EvalDataType {
+    fn from(value: &str) -> EvalDataType {
+        EvalDataType::String(value.to_owned())
+    }
+}
+
+impl From<EvalError> for EvalDataType {
+    fn from(value: EvalError) -> EvalDataType {
+        EvalDataType::Error(value)
+    }
+}
+
+impl<T: Into<EvalDataType>> From<Vec<T>> for EvalDataType {
+    fn from(values: Vec<T>) -> EvalDataType {
+        EvalDataType::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<EvalDataType>> From<BTreeMap<String, T>> for EvalDataType {
+    fn from(entries: BTreeMap<String, T>) -> EvalDataType {
+        EvalDataType::Map(entries.into_iter().map(|(key, value)| (key, value.into())).collect())
+    }
+}
+
+impl<T: Into<EvalDataType>> From<Option<T>> for EvalDataType {
+    fn from(value: Option<T>) -> EvalDataType {
+        value.map_or(EvalDataType::Null, Into::into)
+    }
+}
+
+impl TryFrom<EvalDataType> for u128 {
+    type Error = ConversionError;
+    fn try_from(value: EvalDataType) -> Result<u128, ConversionError> {
+        match value {
+            EvalDataType::Number(number) => Ok(number),
+            found => Err(ConversionError { expected: "number", found }),
+        }
+    }
+}
+
+impl TryFrom<EvalDataType> for u64 {
+    type Error = ConversionError;
+    fn try_from(value: EvalDataType) -> Result<u64, ConversionError> {
+        match value {
+            EvalDataType::Number(number) if number <= u64::MAX as u128 => Ok(number as u64),
+            found => Err(ConversionError { expected: "number that fits in 64 bits", found }),
+        }
+    }
+}
+
+impl TryFrom<EvalDataType> for usize {
+    type Error = ConversionError;
+    fn try_from(value: EvalDataType) -> Result<usize, ConversionError> {
+        match value {
+            EvalDataType::Number(number) if number <= usize::MAX as u128 => Ok(number as usize),
+            found => Err(ConversionError { expected: "number that fits in usize", found }),
+        }
+    }
+}
+
+impl TryFrom<EvalDataType> for bool {
+    type Error = ConversionError;
+    fn try_from(value: EvalDataType) -> Result<bool, ConversionError> {
+        match value {
+            EvalDataType::Bool(value) => Ok(value),
+            found => Err(ConversionError { expected: "bool", found }),
+        }
+    }
+}
+
+impl TryFrom<EvalDataType> for String {
+    type Error = ConversionError;
+    fn try_from(value: EvalDataType) -> Result<String, ConversionError> {
+        match value {
+            EvalDataType::String(text) => Ok(text),
+            found => Err(ConversionError { expected: "string", found }),
+        }
+    }
+}
+
+impl<T: TryFrom<EvalDataType, Error = ConversionError>> TryFrom<EvalDataType> for Vec<T> {
+    type Error = ConversionError;
+    fn try_from(value: EvalDataType) -> Result<Vec<T>, ConversionError> {
+        match value {
+            EvalDataType::List(items) => items.into_iter().map(T::try_from).collect(),
+            found => Err(ConversionError { expected: "list", found }),
+        }
+    }
+}
+
+impl<T: TryFrom<EvalDataType, Error = ConversionError>> TryFrom<EvalDataType> for BTreeMap<String, T> {
+    type Error = ConversionError;
+    fn try_from(value: EvalDataType) -> Result<BTreeMap<String, T>, ConversionError> {
+        match value {
+            EvalDataType::Map(entries) => entries.into_iter().map(|(key, value)| Ok((key, T::try_from(value)?))).collect(),
+            found => Err(ConversionError { expected: "map", found }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use crate::{ConversionError, EvalDataType};
+
+    #[test]
+    fn test_into_values() {
+        assert_eq!(EvalDataType::from(42u64), EvalDataType::Number(42));
+        assert_eq!(EvalDataType::from("eva"), EvalDataType::String("eva".to_owned()));
+        assert_eq!(EvalDataType::from(vec![true, false]), EvalDataType::List(vec![EvalDataType::Bool(true), EvalDataType::Bool(false)]));
+        assert_eq!(EvalDataType::from(None::<bool>), EvalDataType::Null);
+        let ports = BTreeMap::from([("http".to_owned(), 80u64)]);
+        assert_eq!(BTreeMap::<String, u64>::try_from(EvalDataType::from(ports.clone())), Ok(ports));
+    }
+
+    #[test]
+    fn test_from_values() {
+        assert_eq!(u64::try_from(EvalDataType::Number(7)), Ok(7));
+        assert_eq!(Vec::<String>::try_from(EvalDataType::from(vec!["a", "b"])), Ok(vec!["a".to_owned(), "b".to_owned()]));
+        assert_eq!(u64::try_from(EvalDataType::Number(u128::MAX)).unwrap_err().expected, "number that fits in 64 bits");
+        let error: ConversionError = bool::try_from(EvalDataType::from("yes")).unwrap_err();
+        assert_eq!(error.to_string(), "expected bool, found string \"yes\"");
+    }
+}
@@ -0,0 +1,346 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use serde_json::{json, Value};
+use crate::capabilities::Capabilities;
+use crate::debugger::{form_lines, DebugCommand, DebugFrontend, Debugger, Pause, PauseReason};
+use crate::environment_manager_raw::EnvironmentManagerRaw;
+use crate::io_library::Io;
+use crate::parser::parse;
+use crate::{Eva, EvalDataType, Interpreter};
+
+/// Messages of the Debug Adapter Protocol: JSON bodies, each preceded by a `Content-Length` header.
+struct Connection {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: u64,
+}
+
+type Shared = Rc<RefCell<Connection>>;
+
+// `None` once the client has gone away or sent something that is not a message. The Language Server Protocol
+// frames its messages the same way.
+pub(crate) fn read_message(input: &mut dyn BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        match line.trim_end() {
+            "" if length.is_some() => break,
+            "" => {}
+            header => {
+                if let Some(value) = header.strip_prefix("Content-Length:") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+    }
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+pub(crate) fn write_message(output: &mut dyn Write, message: &Value) {
+    let body = message.to_string();
+    let _ = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = output.flush();
+}
+
+impl Connection {
+    fn read(&mut self) -> Option<Value> {
+        read_message(&mut self.input)
+    }
+    fn send(&mut self, mut message: Value) {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message);
+    }
+    fn respond(&mut self, request: &Value, body: Value) {
+        self.send(json!({ "type": "response", "request_seq": request["seq"], "command": request["command"], "success": true, "body": body }));
+    }
+    fn fail(&mut self, request: &Value, message: String) {
+        self.send(json!({ "type": "response", "request_seq": request["seq"], "command": request["command"], "success": false, "message": message }));
+    }
+    fn event(&mut self, event: &str, body: Value) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+}
+
+// Program output goes to the client as `output` events, since standard output carries the protocol.
+struct OutputEvents {
+    connection: Shared,
+    category: &'static str,
+}
+
+impl Write for OutputEvents {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.connection.borrow_mut().event("output", json!({ "category": self.category, "output": String::from_utf8_lossy(bytes) }));
+        Ok(bytes.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Launch {
+    path: String,
+    source: String,
+    // The lines a form starts on, the only ones a breakpoint is verified at.
+    lines: BTreeSet<usize>,
+    stop_on_entry: bool,
+    capabilities: Capabilities,
+}
+
+/// Serves one debugging session: the client launches a program, sets breakpoints and, once configuration is
+/// done, the program runs under the debugger until it ends or the client disconnects.
+pub fn serve(input: impl BufRead + 'static, output: impl Write + 'static) {
+    let connection: Shared = Rc::new(RefCell::new(Connection { input: Box::new(input), output: Box::new(output), seq: 0 }));
+    let mut launch: Option<Launch> = None;
+    let mut configured = false;
+    let mut breakpoints: Vec<usize> = vec![];
+    loop {
+        let request = match connection.borrow_mut().read() {
+            Some(request) => request,
+            None => return,
+        };
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                let mut connection = connection.borrow_mut();
+                connection.respond(&request, json!({ "supportsConfigurationDoneRequest": true, "supportsEvaluateForHovers": true }));
+                connection.event("initialized", json!({}));
+            }
+            "launch" => match read_launch(&request["arguments"]) {
+                Ok(program) => {
+                    connection.borrow_mut().respond(&request, json!({}));
+                    launch = Some(program);
+                }
+                Err(message) => connection.borrow_mut().fail(&request, message),
+            },
+            "setBreakpoints" => {
+                let lines = launch.as_ref().map_or(BTreeSet::new(), |program| program.lines.clone());
+                let (verified, reply) = set_breakpoints(&request["arguments"], &lines);
+                connection.borrow_mut().respond(&request, reply);
+                breakpoints = verified;
+            }
+            "configurationDone" => {
+                connection.borrow_mut().respond(&request, json!({}));
+                configured = true;
+            }
+            "threads" => connection.borrow_mut().respond(&request, json!({ "threads": [{ "id": 1, "name": "main" }] })),
+            "disconnect" => {
+                connection.borrow_mut().respond(&request, json!({}));
+                return;
+            }
+            command => connection.borrow_mut().fail(&request, format!("`{}` needs a paused program", command)),
+        }
+        if let (true, Some(program)) = (configured, &launch) {
+            if !run_program(&connection, program, &breakpoints) {
+                return;
+            }
+            launch = None;
+        }
+    }
+}
+
+fn read_launch(arguments: &Value) -> Result<Launch, String> {
+    let path = arguments["program"].as_str().ok_or("`launch` needs a `program`")?.to_owned();
+    let source = fs::read_to_string(&path).map_err(|error| format!("{}: {}", path, error))?;
+    let flags: Vec<String> = arguments["args"].as_array().map_or(vec![], |args| args.iter().filter_map(|arg| arg.as_str().map(str::to_owned)).collect());
+    let capabilities = Capabilities::from_flags(&flags)?;
+    // A program that does not parse has no breakable lines; the parse error is reported once it is run.
+    let lines = form_lines(&source).unwrap_or_default();
+    Ok(Launch { path, source, lines, stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false), capabilities })
+}
+
+// The requested lines a form starts on, and the reply that marks the others unverified.
+fn set_breakpoints(arguments: &Value, lines: &BTreeSet<usize>) -> (Vec<usize>, Value) {
+    let requested: Vec<usize> = arguments["breakpoints"].as_array()
+        .map_or(vec![], |breakpoints| breakpoints.iter().filter_map(|breakpoint| breakpoint["line"].as_u64()).map(|line| line as usize).collect());
+    let replies: Vec<Value> = requested.iter().map(|line| match lines.contains(line) {
+        true => json!({ "verified": true, "line": line }),
+        false => json!({ "verified": false, "line": line, "message": "no form starts on this line" }),
+    }).collect();
+    (requested.into_iter().filter(|line| lines.contains(line)).collect(), json!({ "breakpoints": replies }))
+}
+
+// Returns false when the client disconnected while the program was paused.
+fn run_program(connection: &Shared, launch: &Launch, breakpoints: &[usize]) -> bool {
+    let disconnected = Rc::new(Cell::new(false));
+    let frontend = DapFrontend { connection: Rc::clone(connection), path: launch.path.clone(), disconnected: Rc::clone(&disconnected) };
+    let debugger = match Debugger::new(&launch.source, frontend) {
+        Ok(debugger) => breakpoints.iter().copied().fold(debugger, Debugger::with_breakpoint),
+        Err(error) => {
+            let mut connection = connection.borrow_mut();
+            connection.event("output", json!({ "category": "stderr", "output": format!("{}: {}\n", launch.path, error) }));
+            connection.event("exited", json!({ "exitCode": 2 }));
+            connection.event("terminated", json!({}));
+            return true;
+        }
+    };
+    let debugger = if launch.stop_on_entry { debugger } else { debugger.without_stop_on_entry() };
+    let io = Io::standard()
+        .with_stdout(OutputEvents { connection: Rc::clone(connection), category: "stdout" })
+        .with_stderr(OutputEvents { connection: Rc::clone(connection), category: "stderr" })
+        .with_stdin(io::empty());
+    let interpreter = Interpreter::new().with_capabilities(launch.capabilities.clone()).with_io(io).with_debugger(debugger);
+    let result = interpreter.eval_str(&launch.source);
+    if disconnected.get() {
+        return false;
+    }
+    let mut connection = connection.borrow_mut();
+    let exit_code = match result {
+        Ok(value) => {
+            connection.event("output", json!({ "category": "console", "output": format!("{}\n", value) }));
+            0
+        }
+        Err(error) => {
+            connection.event("output", json!({ "category": "stderr", "output": format!("{}: {}\n", launch.path, error) }));
+            1
+        }
+    };
+    connection.event("exited", json!({ "exitCode": exit_code }));
+    connection.event("terminated", json!({}));
+    true
+}
+
+struct DapFrontend {
+    connection: Shared,
+    path: String,
+    disconnected: Rc<Cell<bool>>,
+}
+
+impl DebugFrontend for DapFrontend {
+    fn pause(&mut self, pause: Pause) -> DebugCommand {
+        let reason = match pause.reason {
+            PauseReason::Entry => "entry",
+            PauseReason::Breakpoint => "breakpoint",
+            PauseReason::Step => "step",
+        };
+        self.connection.borrow_mut().event("stopped", json!({ "reason": reason, "threadId": 1, "allThreadsStopped": true }));
+        // What each `variablesReference` of this pause expands to; reference N is entry N - 1.
+        let mut handles: Vec<Vec<(String, EvalDataType)>> = vec![];
+        let mut connection = self.connection.borrow_mut();
+        loop {
+            let request = match connection.read() {
+                Some(request) => request,
+                None => {
+                    self.disconnected.set(true);
+                    return DebugCommand::Quit;
+                }
+            };
+            let arguments = &request["arguments"];
+            let command = match request["command"].as_str().unwrap_or_default() {
+                "continue" => Some((DebugCommand::Continue, json!({ "allThreadsContinued": true }))),
+                "next" => Some((DebugCommand::StepOver, json!({}))),
+                "stepIn" => Some((DebugCommand::StepIn, json!({}))),
+                "stepOut" => Some((DebugCommand::StepOut, json!({}))),
+                "disconnect" => {
+                    self.disconnected.set(true);
+                    Some((DebugCommand::Quit, json!({})))
+                }
+                _ => None,
+            };
+            if let Some((command, body)) = command {
+                connection.respond(&request, body);
+                return command;
+            }
+            let innermost = pause.frames.len() - 1;
+            let frame = |arguments: &Value| arguments["frameId"].as_u64().map_or(Some(innermost), |id| (id as usize <= innermost).then_some(innermost - id as usize));
+            match request["command"].as_str().unwrap_or_default() {
+                "threads" => connection.respond(&request, json!({ "threads": [{ "id": 1, "name": "main" }] })),
+                // Frame ids count from the innermost frame, which is 0.
+                "stackTrace" => {
+                    let frames: Vec<Value> = pause.frames.iter().rev().enumerate().map(|(id, frame)| json!({
+                        "id": id,
+                        "name": frame.name,
+                        "line": frame.line.unwrap_or(pause.line),
+                        "column": if id == 0 { pause.column } else { 1 },
+                        "source": { "path": self.path },
+                    })).collect();
+                    connection.respond(&request, json!({ "stackFrames": frames, "totalFrames": frames.len() }));
+                }
+                "scopes" => match frame(arguments) {
+                    Some(index) => {
+                        let scopes = pause.scopes(index);
+                        let last = scopes.len() - 1;
+                        let scopes: Vec<Value> = scopes.into_iter().enumerate().map(|(depth, bindings)| {
+                            let name = match depth {
+                                depth if depth == last => "Globals".to_owned(),
+                                0 => "Locals".to_owned(),
+                                depth => format!("Enclosing {}", depth),
+                            };
+                            handles.push(bindings);
+                            json!({ "name": name, "variablesReference": handles.len(), "expensive": false })
+                        }).collect();
+                        connection.respond(&request, json!({ "scopes": scopes }));
+                    }
+                    None => connection.fail(&request, format!("no frame {}", arguments["frameId"])),
+                },
+                "variables" => match arguments["variablesReference"].as_u64().and_then(|reference| handles.get((reference as usize).wrapping_sub(1))).cloned() {
+                    Some(bindings) => {
+                        let variables: Vec<Value> = bindings.into_iter().map(|(name, value)| {
+                            let (text, reference) = describe(value, &mut handles);
+                            json!({ "name": name, "value": text, "variablesReference": reference })
+                        }).collect();
+                        connection.respond(&request, json!({ "variables": variables }));
+                    }
+                    None => connection.fail(&request, format!("no variables {}", arguments["variablesReference"])),
+                },
+                "evaluate" => match (frame(arguments), arguments["expression"].as_str()) {
+                    (Some(index), Some(expression)) => match evaluate(expression, &pause.frames[index].env) {
+                        Ok(value) => {
+                            let (text, reference) = describe(value, &mut handles);
+                            connection.respond(&request, json!({ "result": text, "variablesReference": reference }));
+                        }
+                        Err(message) => connection.fail(&request, message),
+                    },
+                    _ => connection.fail(&request, "`evaluate` needs an expression and a valid frame".to_owned()),
+                },
+                "setBreakpoints" => {
+                    let (verified, reply) = set_breakpoints(arguments, pause.lines);
+                    *pause.breakpoints = verified.into_iter().collect();
+                    connection.respond(&request, reply);
+                }
+                command => connection.fail(&request, format!("`{}` is not supported", command)),
+            }
+        }
+    }
+}
+
+// The text of a value, and a reference to expand it by when it is a list or a map.
+fn describe(value: EvalDataType, handles: &mut Vec<Vec<(String, EvalDataType)>>) -> (String, usize) {
+    let children: Option<Vec<(String, EvalDataType)>> = match &value {
+        EvalDataType::List(items) => Some(items.iter().enumerate().map(|(index, item)| (index.to_string(), item.clone())).collect()),
+        EvalDataType::Map(entries) => Some(entries.iter().map(|(key, item)| (key.clone(), item.clone())).collect()),
+        _ => None,
+    };
+    match children {
+        Some(children) => {
+            handles.push(children);
+            (value.to_string(), handles.len())
+        }
+        None => (value.to_string(), 0),
+    }
+}
+
+// Evaluated in the frame's own environment, so `set` changes the paused program, but by an evaluator of its own
+// that has no capabilities and no debugger.
+fn evaluate(expression: &str, env: &Rc<RefCell<EnvironmentManagerRaw>>) -> Result<EvalDataType, String> {
+    let program = parse(expression).map_err(|error| error.to_string())?;
+    let eva = Eva::new().with_capabilities(Capabilities::none());
+    program.into_iter().try_fold(EvalDataType::Null, |_, exp| eva.eval_value(&[exp], Rc::clone(env))).map_err(|error| error.to_string())
+}
+
+/// `eva dap`: a Debug Adapter Protocol server on standard input and output, for debugging from an editor.
+pub fn run(args: &[String]) -> i32 {
+    if !args.is_empty() {
+        eprintln!("usage: eva dap");
+        return 2;
+    }
+    serve(io::stdin().lock(), io::stdout());
+    0
+}
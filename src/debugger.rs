@@ -0,0 +1,440 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use crate::capabilities::{Capabilities, ALLOW_FLAGS};
+use crate::environment_manager_raw::EnvironmentManagerRaw;
+use crate::parser::{parse, parse_syntax, ParseError, SyntaxNode};
+use crate::printer::print_inline;
+use crate::{EvalDataType, EvalType, Interpreter, InterpreterError, Span};
+
+type Environment = Rc<RefCell<EnvironmentManagerRaw>>;
+
+/// What the front end tells a paused program to do next.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DebugCommand {
+    Continue,
+    /// Pause at the very next form, inside calls too.
+    StepIn,
+    /// Pause at the next form that is not part of the current one.
+    StepOver,
+    /// Pause once the current function has returned.
+    StepOut,
+    /// Abandon the program; evaluation fails with `InterpreterError::Stopped`.
+    Quit,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PauseReason {
+    Entry,
+    Breakpoint,
+    Step,
+}
+
+/// A call in progress, innermost last; the first frame is the top level, named `<main>`.
+#[derive(Clone)]
+pub struct Frame {
+    pub name: String,
+    /// The line of the form the frame is evaluating, when it is known.
+    pub line: Option<usize>,
+    pub env: Environment,
+}
+
+/// The bindings of one environment, sorted by name.
+pub type Scope = Vec<(String, EvalDataType)>;
+
+/// The program as seen from a pause.
+pub struct Pause<'a> {
+    pub reason: PauseReason,
+    pub line: usize,
+    pub column: usize,
+    /// The form about to be evaluated, printed on one line.
+    pub form: String,
+    pub frames: &'a [Frame],
+    pub breakpoints: &'a mut BTreeSet<usize>,
+    /// The lines a form starts on, from `form_lines`; a breakpoint anywhere else is never hit.
+    pub lines: &'a BTreeSet<usize>,
+}
+
+impl Pause<'_> {
+    /// The environments visible from `frame`, innermost first and the globals last.
+    pub fn scopes(&self, frame: usize) -> Vec<Scope> {
+        scopes(&self.frames[frame].env)
+    }
+}
+
+pub fn scopes(env: &Environment) -> Vec<Scope> {
+    let mut scopes = vec![];
+    let mut next = Some(Rc::clone(env));
+    while let Some(env) = next {
+        let env = env.borrow();
+        let mut bindings: Scope = env.env.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        scopes.push(bindings);
+        next = env.parent().cloned();
+    }
+    scopes
+}
+
+/// The user side of a debugging session: it is called at every pause and decides how to go on.
+pub trait DebugFrontend {
+    fn pause(&mut self, pause: Pause) -> DebugCommand;
+}
+
+/// The lines on which a list form of `source` starts: the only lines a breakpoint can stop at.
+pub fn form_lines(source: &str) -> Result<BTreeSet<usize>, ParseError> {
+    fn add(node: &SyntaxNode, lines: &mut BTreeSet<usize>) {
+        if let SyntaxNode::List { items, line, .. } = node {
+            lines.insert(*line);
+            items.iter().for_each(|item| add(item, lines));
+        }
+    }
+    parse(source)?;
+    let mut lines = BTreeSet::new();
+    parse_syntax(source)?.iter().for_each(|node| add(node, &mut lines));
+    Ok(lines)
+}
+
+/// Pauses `Eva::eval` before forms, for line breakpoints and stepping. Forms are located by the spans the parser
+/// gave them, so the program must be evaluated as parsed from the same source, without the optimizer.
+pub struct Debugger {
+    lines: BTreeSet<usize>,
+    breakpoints: BTreeSet<usize>,
+    frontend: Box<dyn DebugFrontend>,
+    frames: Vec<Frame>,
+    // The span of every form being evaluated, outermost first.
+    forms: Vec<Span>,
+    mode: Mode,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Mode {
+    Entry,
+    Continue,
+    StepIn,
+    StepOver { frames: usize, forms: usize },
+    StepOut { frames: usize },
+}
+
+impl Debugger {
+    /// A debugger that pauses at the first form of `source`.
+    pub fn new(source: &str, frontend: impl DebugFrontend + 'static) -> Result<Debugger, ParseError> {
+        Ok(Debugger {
+            lines: form_lines(source)?,
+            breakpoints: BTreeSet::new(),
+            frontend: Box::new(frontend),
+            frames: vec![],
+            forms: vec![],
+            mode: Mode::Entry,
+        })
+    }
+    /// Runs to the first breakpoint instead of pausing at the first form.
+    pub fn without_stop_on_entry(self) -> Debugger {
+        Debugger { mode: Mode::Continue, ..self }
+    }
+    pub fn with_breakpoint(mut self, line: usize) -> Debugger {
+        self.breakpoints.insert(line);
+        self
+    }
+    /// Fails with `InterpreterError::Stopped` when the front end quits at a pause.
+    pub(crate) fn enter_form(&mut self, form: &[EvalType], span: Span, env: &Environment) -> Result<(), InterpreterError> {
+        if self.frames.is_empty() {
+            self.frames.push(Frame { name: "<main>".to_owned(), line: None, env: Rc::clone(env) });
+        }
+        // Lists the evaluator built itself were never written anywhere, so they are not paused at.
+        if span.is_known() {
+            if let Some(frame) = self.frames.last_mut() {
+                frame.line = Some(span.line);
+                frame.env = Rc::clone(env);
+            }
+            if let Some(reason) = self.pause_reason(span) {
+                self.pause(reason, span, form)?;
+            }
+        }
+        self.forms.push(span);
+        Ok(())
+    }
+    pub(crate) fn leave_form(&mut self) {
+        self.forms.pop();
+    }
+    pub(crate) fn enter_function(&mut self, name: &str, env: &Environment) {
+        self.frames.push(Frame { name: name.to_owned(), line: None, env: Rc::clone(env) });
+    }
+    pub(crate) fn leave_function(&mut self) {
+        self.frames.pop();
+    }
+    fn pause_reason(&self, span: Span) -> Option<PauseReason> {
+        // Only the outermost form written on a line stops at its breakpoint.
+        let enclosing_line = self.forms.iter().rev().find(|outer| outer.is_known()).map(|outer| outer.line);
+        let (frames, forms) = (self.frames.len(), self.forms.len());
+        match self.mode {
+            Mode::Entry => Some(PauseReason::Entry),
+            Mode::StepIn => Some(PauseReason::Step),
+            Mode::StepOver { frames: from_frames, forms: from_forms } if frames < from_frames || (frames == from_frames && forms <= from_forms) => Some(PauseReason::Step),
+            Mode::StepOut { frames: from_frames } if frames < from_frames => Some(PauseReason::Step),
+            _ if self.breakpoints.contains(&span.line) && enclosing_line != Some(span.line) => Some(PauseReason::Breakpoint),
+            _ => None,
+        }
+    }
+    fn pause(&mut self, reason: PauseReason, span: Span, form: &[EvalType]) -> Result<(), InterpreterError> {
+        let pause = Pause {
+            reason,
+            line: span.line,
+            column: span.column,
+            form: print_inline(&EvalType::Content(form.to_vec(), span)),
+            frames: &self.frames,
+            breakpoints: &mut self.breakpoints,
+            lines: &self.lines,
+        };
+        self.mode = match self.frontend.pause(pause) {
+            DebugCommand::Continue => Mode::Continue,
+            DebugCommand::StepIn => Mode::StepIn,
+            DebugCommand::StepOver => Mode::StepOver { frames: self.frames.len(), forms: self.forms.len() },
+            DebugCommand::StepOut => Mode::StepOut { frames: self.frames.len() },
+            DebugCommand::Quit => return Err(InterpreterError::Stopped),
+        };
+        Ok(())
+    }
+}
+
+const HELP: &str = "commands:
+  s, step        step into the next form
+  n, next        step over the current form
+  o, out         run until the current function returns
+  c, continue    run until a breakpoint
+  b, break N     add a breakpoint at line N
+  d, delete N    remove the breakpoint at line N
+  bt, backtrace  show the call stack
+  scopes [N]     show the scopes of frame N, the innermost by default
+  p, print NAME  show a variable
+  q, quit        stop the program";
+
+/// The command line front end of `eva debug`, reading commands from `input` and writing to `output`.
+pub struct ConsoleFrontend<R: BufRead, W: Write> {
+    path: String,
+    lines: Vec<String>,
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> ConsoleFrontend<R, W> {
+    pub fn new(path: &str, source: &str, input: R, output: W) -> ConsoleFrontend<R, W> {
+        ConsoleFrontend { path: path.to_owned(), lines: source.lines().map(str::to_owned).collect(), input, output }
+    }
+    fn command(&mut self, pause: &mut Pause, line: &str) -> Option<DebugCommand> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let innermost = pause.frames.len().saturating_sub(1);
+        let output = &mut self.output;
+        let _ = match words.as_slice() {
+            ["s" | "step"] => return Some(DebugCommand::StepIn),
+            ["n" | "next"] => return Some(DebugCommand::StepOver),
+            ["o" | "out"] => return Some(DebugCommand::StepOut),
+            ["c" | "continue"] => return Some(DebugCommand::Continue),
+            ["q" | "quit"] => return Some(DebugCommand::Quit),
+            ["b" | "break" | "d" | "delete", line] => match (line.parse::<usize>(), words[0].starts_with('b')) {
+                (Ok(line), true) if !pause.lines.contains(&line) => writeln!(output, "no form starts on line {}", line).map(|_| false),
+                (Ok(line), true) => writeln!(output, "breakpoint at line {}", line).map(|_| pause.breakpoints.insert(line)),
+                (Ok(line), false) => writeln!(output, "removed breakpoint at line {}", line).map(|_| pause.breakpoints.remove(&line)),
+                (Err(_), _) => writeln!(output, "not a line number: {}", line).map(|_| false),
+            },
+            ["bt" | "backtrace"] => pause.frames.iter().enumerate().rev().try_for_each(|(index, frame)| {
+                let line = frame.line.map_or("?".to_owned(), |line| line.to_string());
+                writeln!(output, "#{} {} at {}:{}", innermost - index, frame.name, self.path, line)
+            }).map(|_| true),
+            ["scopes"] | ["scopes", _] => match words.get(1).map_or(Ok(0), |frame| frame.parse::<usize>()) {
+                Ok(frame) if frame <= innermost => pause.scopes(innermost - frame).iter().enumerate().try_for_each(|(depth, scope)| {
+                    writeln!(output, "scope {}:", depth)?;
+                    scope.iter().try_for_each(|(name, value)| writeln!(output, "  {} = {}", name, value))
+                }).map(|_| true),
+                _ => writeln!(output, "no frame {}", words[1]).map(|_| false),
+            },
+            ["p" | "print", name] => match pause.frames[innermost].env.borrow().get(name.to_string()) {
+                Some(value) => writeln!(output, "{} = {}", name, value),
+                None => writeln!(output, "`{}` is not defined here", name),
+            }.map(|_| true),
+            ["help"] | ["h"] => writeln!(output, "{}", HELP).map(|_| true),
+            [] => Ok(false),
+            _ => writeln!(output, "unknown command `{}`; try help", line.trim()).map(|_| false),
+        };
+        None
+    }
+}
+
+impl<R: BufRead, W: Write> DebugFrontend for ConsoleFrontend<R, W> {
+    fn pause(&mut self, mut pause: Pause) -> DebugCommand {
+        let text = self.lines.get(pause.line - 1).map_or("", |text| text.trim());
+        let marker = if pause.reason == PauseReason::Breakpoint { " (breakpoint)" } else { "" };
+        let _ = writeln!(self.output, "{}:{}{}: {}", self.path, pause.line, marker, text);
+        loop {
+            let _ = write!(self.output, "(debug) ");
+            let _ = self.output.flush();
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) | Err(_) => return DebugCommand::Continue,
+                Ok(_) => {}
+            }
+            if let Some(command) = self.command(&mut pause, &line) {
+                return command;
+            }
+        }
+    }
+}
+
+/// `eva debug [--allow-…] [--break=LINE]… file`: runs a program under the console debugger, paused at its first form.
+pub fn run(args: &[String]) -> i32 {
+    let files: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let breakpoints: Result<Vec<usize>, String> = args.iter()
+        .filter_map(|arg| arg.strip_prefix("--break="))
+        .map(|line| line.parse::<usize>().map_err(|_| format!("not a line number: {}", line)))
+        .collect();
+    let flags: Vec<String> = args.iter().filter(|arg| !arg.starts_with("--break=")).cloned().collect();
+    let (file, breakpoints, capabilities) = match (files.as_slice(), breakpoints, Capabilities::from_flags(&flags)) {
+        ([file], Ok(breakpoints), Ok(capabilities)) => (file, breakpoints, capabilities),
+        (_, Err(error), _) | (_, _, Err(error)) => {
+            eprintln!("{}", error);
+            return 2;
+        }
+        _ => {
+            eprintln!("usage: eva debug {} [--break=<line>]... <file>", ALLOW_FLAGS);
+            return 2;
+        }
+    };
+    let source = match fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("{}: {}", file, error);
+            return 2;
+        }
+    };
+    let frontend = ConsoleFrontend::new(file, &source, io::stdin().lock(), io::stdout());
+    let debugger = match Debugger::new(&source, frontend) {
+        Ok(debugger) => breakpoints.into_iter().fold(debugger, |debugger, line| {
+            if !debugger.lines.contains(&line) {
+                eprintln!("{}: no form starts on line {}", file, line);
+            }
+            debugger.with_breakpoint(line)
+        }),
+        Err(error) => {
+            eprintln!("{}: {}", file, error);
+            return 2;
+        }
+    };
+    let interpreter = Interpreter::new().with_capabilities(capabilities).with_debugger(debugger);
+    match interpreter.eval_str(&source) {
+        Ok(value) => {
+            println!("{}", value);
+            0
+        }
+        Err(error) => {
+            eprintln!("{}: {}", file, error);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::io_library::Capture;
+    use crate::debugger::{ConsoleFrontend, DebugCommand, DebugFrontend, Debugger, Pause, PauseReason};
+    use crate::{EvalDataType, Interpreter, InterpreterError};
+
+    const PROGRAM: &str = "(def double (x)
+  (* x 2))
+(var total 0)
+(var i 0)
+(while ((< i 3)
+  (begin ((set total (+ total (double i)))
+    (set i (+ i 1))))))
+total
+";
+
+    // Answers every pause with the next scripted command and records where it paused.
+    struct Script {
+        commands: Vec<DebugCommand>,
+        pauses: Rc<RefCell<Vec<(PauseReason, usize, String)>>>,
+    }
+
+    impl DebugFrontend for Script {
+        fn pause(&mut self, pause: Pause) -> DebugCommand {
+            let names: Vec<String> = pause.frames.iter().map(|frame| frame.name.clone()).collect();
+            self.pauses.borrow_mut().push((pause.reason, pause.line, names.join(" > ")));
+            match self.commands.is_empty() {
+                true => DebugCommand::Continue,
+                false => self.commands.remove(0),
+            }
+        }
+    }
+
+    fn debug(commands: Vec<DebugCommand>, breakpoints: &[usize]) -> Vec<(PauseReason, usize, String)> {
+        let pauses = Rc::new(RefCell::new(vec![]));
+        let debugger = Debugger::new(PROGRAM, Script { commands, pauses: Rc::clone(&pauses) }).unwrap();
+        let debugger = breakpoints.iter().copied().fold(debugger, Debugger::with_breakpoint);
+        assert_eq!(Interpreter::new().with_debugger(debugger).eval_str(PROGRAM), Ok(EvalDataType::Number(6)));
+        let pauses = pauses.borrow().clone();
+        pauses
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let pauses = debug(vec![DebugCommand::Continue], &[2, 7]);
+        let lines: Vec<(PauseReason, usize)> = pauses.iter().map(|(reason, line, _)| (*reason, *line)).collect();
+        assert_eq!(lines, vec![
+            (PauseReason::Entry, 1),
+            (PauseReason::Breakpoint, 2), (PauseReason::Breakpoint, 7),
+            (PauseReason::Breakpoint, 2), (PauseReason::Breakpoint, 7),
+            (PauseReason::Breakpoint, 2), (PauseReason::Breakpoint, 7),
+        ]);
+        assert_eq!(pauses[1].2, "<main> > double");
+    }
+
+    #[test]
+    fn test_stepping() {
+        use DebugCommand::{Continue, StepIn, StepOut, StepOver};
+        let pauses = debug(vec![StepOver, StepOver, StepOver, StepIn, StepIn, StepIn, StepIn, StepIn, StepIn, StepOut, Continue], &[]);
+        let lines: Vec<usize> = pauses.iter().map(|(_, line, _)| *line).collect();
+        // Over the definitions to the loop, then into it down to `double`, and out to the caller's next form.
+        assert_eq!(lines, vec![1, 3, 4, 5, 5, 6, 6, 6, 6, 2, 7]);
+        assert_eq!(pauses[9].2, "<main> > double");
+        assert_eq!(pauses[10].2, "<main>");
+    }
+
+    #[test]
+    fn test_repeated_forms() {
+        let program = "(var i 0)
+(set i (+ i 1))
+(def twice (x)
+  (* x 2))
+(set i (+ i 1))
+(def double (x)
+  (* x 2))
+(+ (twice i) (double i))
+";
+        let pauses = Rc::new(RefCell::new(vec![]));
+        let script = Script { commands: vec![DebugCommand::Continue, DebugCommand::Continue, DebugCommand::StepOver], pauses: Rc::clone(&pauses) };
+        let debugger = Debugger::new(program, script).unwrap().with_breakpoint(5).with_breakpoint(7);
+        assert_eq!(Interpreter::new().with_debugger(debugger).eval_str(program), Ok(EvalDataType::Number(8)));
+        // The second `(set i (+ i 1))` and the body of `double` are found on their own lines, not on the first copy's.
+        assert_eq!(pauses.borrow().clone(), vec![
+            (PauseReason::Entry, 1, "<main>".to_owned()),
+            (PauseReason::Breakpoint, 5, "<main>".to_owned()),
+            (PauseReason::Breakpoint, 7, "<main> > double".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_console() {
+        let input = "b 8\nb 2\nc\nbt\nscopes\np total\np nothing\nq\n";
+        let output = Capture::new();
+        let frontend = ConsoleFrontend::new("loop.eva", PROGRAM, input.as_bytes(), output.clone());
+        let result = Interpreter::new().with_debugger(Debugger::new(PROGRAM, frontend).unwrap()).eval_str(PROGRAM);
+        assert_eq!(result, Err(InterpreterError::Stopped));
+        let output = output.text();
+        assert!(output.starts_with("loop.eva:1: (def double (x)\n(debug) no form starts on line 8\n(debug) breakpoint at line 2\n(debug) loop.eva:2 (breakpoint): (* x 2))\n"), "{}", output);
+        assert!(output.contains("#0 double at loop.eva:2\n#1 <main> at loop.eva:6\n"), "{}", output);
+        assert!(output.contains("scope 0:\n  x = 0\nscope 1:\n"), "{}", output);
+        assert!(output.contains("total = 0\n(debug) `nothing` is not defined here\n"), "{}", output);
+    }
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Strictness {
+    Strict,
+    Lenient,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn warning(message: String) -> Diagnostic {
+        Diagnostic { severity: Severity::Warning, message }
+    }
+    pub fn error(message: String) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, message }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Picks the candidate closest to `name`, ignoring anything too far away to be a plausible typo.
+pub fn closest_name(name: &str, candidates: &[String]) -> Option<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates.iter()
+        .filter(|candidate| candidate.as_str() != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by(|(a, a_name), (b, b_name)| a.cmp(b).then_with(|| a_name.cmp(b_name)))
+        .map(|(_, candidate)| candidate.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::{closest_name, edit_distance};
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("counter", "counter"), 0);
+        assert_eq!(edit_distance("countr", "counter"), 1);
+        assert_eq!(edit_distance("x", "y"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("ñandú", "nandu"), 2);
+    }
+
+    #[test]
+    fn test_closest_name() {
+        let candidates = vec!["counter".to_owned(), "result".to_owned(), "VERSION".to_owned()];
+        assert_eq!(closest_name("countr", &candidates), Some("counter".to_owned()));
+        assert_eq!(closest_name("reslt", &candidates), Some("result".to_owned()));
+        assert_eq!(closest_name("total", &candidates), None);
+    }
+}
@@ -1,43 +1,128 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
+use im_rc::{HashMap as PersistentMap, HashSet as PersistentSet};
+use crate::diagnostics::closest_name;
 use crate::EvalDataType;
 
+#[derive(PartialEq, Debug, Clone)]
+pub enum EnvironmentError {
+    Redeclaration { name: String },
+    UndeclaredAssignment { name: String, suggestion: Option<String> },
+    ConstantAssignment { name: String },
+}
+
+impl fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvironmentError::Redeclaration { name } => write!(f, "variable `{}` is already declared in this scope", name),
+            EnvironmentError::UndeclaredAssignment { name, suggestion: Some(suggestion) } => write!(f, "assignment to undeclared variable `{}`; did you mean `{}`?", name, suggestion),
+            EnvironmentError::UndeclaredAssignment { name, suggestion: None } => write!(f, "assignment to undeclared variable `{}`", name),
+            EnvironmentError::ConstantAssignment { name } => write!(f, "cannot assign to constant `{}`", name),
+        }
+    }
+}
+
+/// The bindings of one environment at some point in time. Taking one is O(1): the maps are persistent, so the
+/// snapshot and the environment share structure and later changes only copy the paths they touch.
+#[derive(Clone, Debug)]
+pub struct EnvironmentSnapshot {
+    env: PersistentMap<String, EvalDataType>,
+    constants: PersistentSet<String>,
+}
+
 pub struct EnvironmentManagerRaw {
-    pub env: HashMap<String, EvalDataType>,
+    pub env: PersistentMap<String, EvalDataType>,
+    constants: PersistentSet<String>,
     parent: Option<Rc<RefCell<EnvironmentManagerRaw>>>,
 }
 
 impl EnvironmentManagerRaw {
     pub fn new(env: Option<HashMap<String, EvalDataType>>, parent: Option<Rc<RefCell<EnvironmentManagerRaw>>>) -> EnvironmentManagerRaw {
         EnvironmentManagerRaw {
-            env: env.unwrap_or(HashMap::new()),
+            env: env.unwrap_or_default().into_iter().collect(),
+            constants: PersistentSet::new(),
             parent,
         }
     }
-    pub fn define(&mut self, name: String, value: EvalDataType) -> EvalDataType {
+    pub fn snapshot(&self) -> EnvironmentSnapshot {
+        EnvironmentSnapshot { env: self.env.clone(), constants: self.constants.clone() }
+    }
+    /// Puts back the bindings of `snapshot`; the parent is left as it is.
+    pub fn restore(&mut self, snapshot: &EnvironmentSnapshot) {
+        self.env = snapshot.env.clone();
+        self.constants = snapshot.constants.clone();
+    }
+    /// Removes every binding and constant; the parent is left as it is.
+    pub fn clear(&mut self) {
+        self.env.clear();
+        self.constants.clear();
+    }
+    pub fn define(&mut self, name: String, value: EvalDataType) -> Result<EvalDataType, EnvironmentError> {
+        if self.env.contains_key(&name) {
+            return Err(EnvironmentError::Redeclaration { name });
+        }
+        self.env.insert(name, value.clone());
+        Ok(value)
+    }
+    pub fn redefine(&mut self, name: String, value: EvalDataType) -> EvalDataType {
         self.env.insert(name, value.clone());
         value
     }
-    pub fn assign(&mut self, name: String, value: EvalDataType) -> Option<EvalDataType> {
-        if self.env.contains_key(&name) {
-            self.env.insert(name, value.clone());
-            return Some(value);
-        } else {
-            if let Some(parent) = &self.parent {
-                return parent.borrow_mut().assign(name, value);
-            }
+    pub fn parent(&self) -> Option<&Rc<RefCell<EnvironmentManagerRaw>>> {
+        self.parent.as_ref()
+    }
+    pub fn mark_constant(&mut self, name: &str) {
+        self.constants.insert(name.to_owned());
+    }
+    pub fn constants(&self) -> impl Iterator<Item = &String> {
+        self.constants.iter()
+    }
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.constants.contains(name)
+    }
+    pub fn assign(&mut self, name: String, value: EvalDataType) -> Result<EvalDataType, EnvironmentError> {
+        if let Some(assigned) = self.assign_existing(&name, value) {
+            return assigned;
+        }
+        let suggestion = closest_name(&name, &self.visible_names());
+        Err(EnvironmentError::UndeclaredAssignment { name, suggestion })
+    }
+    fn assign_existing(&mut self, name: &str, value: EvalDataType) -> Option<Result<EvalDataType, EnvironmentError>> {
+        if self.constants.contains(name) {
+            return Some(Err(EnvironmentError::ConstantAssignment { name: name.to_owned() }));
+        }
+        if let Some(slot) = self.env.get_mut(name) {
+            *slot = value.clone();
+            return Some(Ok(value));
+        }
+        if let Some(parent) = &self.parent {
+            return parent.borrow_mut().assign_existing(name, value);
         }
         None
     }
     pub fn get(&self, name: String) -> Option<EvalDataType> {
         if let Some(value) = self.env.get(&name) {
             return Some(value.clone());
-        } else {
-            if let Some(parent) = &self.parent {
-                return parent.borrow().get(name);
-            }
+        } else if let Some(parent) = &self.parent {
+            return parent.borrow().get(name);
         }
         None
     }
-}
\ No newline at end of file
+    pub fn is_shadowing(&self, name: &str) -> bool {
+        match &self.parent {
+            Some(parent) => parent.borrow().get(name.to_owned()).is_some(),
+            None => false,
+        }
+    }
+    pub fn visible_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.env.keys().cloned().collect();
+        if let Some(parent) = &self.parent {
+            names.extend(parent.borrow().visible_names());
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+}
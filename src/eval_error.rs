@@ -0,0 +1,47 @@
+use std::fmt;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ErrorKind {
+    IndexOutOfRange,
+    NotFound,
+    TypeMismatch,
+    Arity,
+    InvalidFormat,
+    NotSerializable,
+    Io,
+    AccessDenied,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct EvalError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl EvalError {
+    pub fn new(kind: ErrorKind, message: String) -> EvalError {
+        EvalError { kind, message }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorKind::IndexOutOfRange => "index-out-of-range",
+            ErrorKind::NotFound => "not-found",
+            ErrorKind::TypeMismatch => "type-mismatch",
+            ErrorKind::Arity => "arity",
+            ErrorKind::InvalidFormat => "invalid-format",
+            ErrorKind::NotSerializable => "not-serializable",
+            ErrorKind::Io => "io",
+            ErrorKind::AccessDenied => "access-denied",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
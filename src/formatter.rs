@@ -0,0 +1,119 @@
+use std::fs;
+use crate::parser::{parse_syntax, ParseError};
+use crate::printer::print_nodes;
+
+pub fn format_source(source: &str) -> Result<String, ParseError> {
+    Ok(print_nodes(&parse_syntax(source)?))
+}
+
+/// `eva fmt [--check] files…`: rewrites each file in place, or with `--check` only reports the ones that would change.
+pub fn run(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let files: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+    if files.is_empty() {
+        eprintln!("usage: eva fmt [--check] <files>...");
+        return 2;
+    }
+    let mut status = 0;
+    for file in files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", file, error);
+                status = 2;
+                continue;
+            }
+        };
+        let formatted = match format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("{}:{}", file, error);
+                status = 2;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{} would be reformatted", file);
+            status = status.max(1);
+        } else if let Err(error) = fs::write(file, formatted) {
+            eprintln!("{}: {}", file, error);
+            status = 2;
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::formatter::{format_source, run};
+
+    #[test]
+    fn test_format_special_forms() {
+        let source = "(begin (var x 10)   (while ((< x 20) (set x (+ x 1))))\n (if (> x 10) \"big\" \"small\"))";
+        assert_eq!(format_source(source).unwrap(), concat!(
+            "(begin\n",
+            "  (var x 10)\n",
+            "  (while ((< x 20) (set x (+ x 1))))\n",
+            "  (if (> x 10) \"big\" \"small\"))\n",
+        ));
+    }
+
+    #[test]
+    fn test_format_keeps_comments_and_blank_lines() {
+        let source = concat!(
+            ";; counts to ten\n",
+            "(var counter 0)   ; start\n",
+            "\n\n\n",
+            "(if (< counter 10)\n",
+            "     ; step\n",
+            "  (set counter (+ counter 1)))\n",
+        );
+        let formatted = format_source(source).unwrap();
+        assert_eq!(formatted, concat!(
+            ";; counts to ten\n",
+            "(var counter 0) ; start\n",
+            "\n",
+            "(if (< counter 10)\n",
+            "  ; step\n",
+            "  (set counter (+ counter 1)))\n",
+        ));
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_class_and_def() {
+        let source = "(class Point null (def constructor (this x y) (set (prop this x) x) (set (prop this y) y)))";
+        assert_eq!(format_source(source).unwrap(), concat!(
+            "(class Point null\n",
+            "  (def constructor (this x y)\n",
+            "    (set (prop this x) x)\n",
+            "    (set (prop this y) y)))\n",
+        ));
+    }
+
+    #[test]
+    fn test_format_comment_before_closing_paren() {
+        let formatted = format_source("(begin (var x 1) ; done\n)").unwrap();
+        assert_eq!(formatted, "(begin\n  (var x 1) ; done\n)\n");
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_run_check_and_write() {
+        let dir = std::env::temp_dir().join(format!("eva-fmt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("script.eva");
+        fs::write(&file, "(begin (var x 1) (var y 2))").unwrap();
+        let args = vec!["--check".to_owned(), file.to_string_lossy().into_owned()];
+
+        assert_eq!(run(&args), 1);
+        assert_eq!(run(&args[1..]), 0);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "(begin\n  (var x 1)\n  (var y 2))\n");
+        assert_eq!(run(&args), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use crate::eval_error::{ErrorKind, EvalError};
+use crate::string_library::{expect_arity, expect_string};
+use crate::EvalDataType;
+
+pub const FS_BUILTINS: [&str; 6] = ["file-read", "file-write", "file-append", "file-exists?", "list-dir", "delete-file"];
+
+pub fn is_fs_builtin(name: &str) -> bool {
+    FS_BUILTINS.contains(&name)
+}
+
+/// The directory the file built-ins are confined to, granted with `Capabilities::fs`. Script paths are relative to
+/// it; absolute paths, `..` above it and symbolic links leading out of it fail with an `access-denied` error.
+#[derive(PartialEq, Debug, Clone)]
+pub struct FileSystem {
+    root: PathBuf,
+}
+
+impl FileSystem {
+    pub fn new(root: impl AsRef<Path>) -> io::Result<FileSystem> {
+        let root = root.as_ref().canonicalize()?;
+        match root.is_dir() {
+            true => Ok(FileSystem { root }),
+            false => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a directory", root.display()))),
+        }
+    }
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    /// The real path `path` names inside the root.
+    pub fn resolve(&self, name: &str, path: &str) -> Result<PathBuf, EvalError> {
+        let denied = || EvalError::new(ErrorKind::AccessDenied, format!("`{}`: {:?} is outside the sandbox", name, path));
+        let mut resolved = self.root.clone();
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::CurDir => {}
+                Component::ParentDir if resolved != self.root => {
+                    resolved.pop();
+                }
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => return Err(denied()),
+            }
+        }
+        // Opening the path follows every link on it, and creating a file through a dangling link creates its target,
+        // so each link must resolve inside the root.
+        let mut prefix = self.root.clone();
+        for part in resolved.strip_prefix(&self.root).map_err(|_| denied())?.components() {
+            prefix.push(part);
+            let link = fs::symlink_metadata(&prefix).is_ok_and(|metadata| metadata.file_type().is_symlink());
+            if link && !prefix.canonicalize().is_ok_and(|real| real.starts_with(&self.root)) {
+                return Err(denied());
+            }
+        }
+        // A path that does not exist yet is checked through its parent directory.
+        let real = match (resolved.canonicalize(), resolved.parent(), resolved.file_name()) {
+            (Ok(real), _, _) => real,
+            (Err(_), Some(parent), Some(file)) => parent.canonicalize().map(|parent| parent.join(file)).unwrap_or(resolved),
+            (Err(_), _, _) => resolved,
+        };
+        match real.starts_with(&self.root) {
+            true => Ok(real),
+            false => Err(denied()),
+        }
+    }
+}
+
+pub fn call(name: &str, args: &[EvalDataType], filesystem: &FileSystem) -> EvalDataType {
+    let result = match name {
+        "file-read" => file_read(args, filesystem),
+        "file-write" => file_write(name, args, filesystem, false),
+        "file-append" => file_write(name, args, filesystem, true),
+        "file-exists?" => file_exists(args, filesystem),
+        "list-dir" => list_dir(args, filesystem),
+        "delete-file" => delete_file(args, filesystem),
+        _ => Err(EvalError::new(ErrorKind::NotFound, format!("`{}` is not a file built-in", name))),
+    };
+    result.unwrap_or_else(EvalDataType::Error)
+}
+
+fn io_error(name: &str, path: &str, error: io::Error) -> EvalError {
+    let kind = match error.kind() {
+        io::ErrorKind::NotFound => ErrorKind::NotFound,
+        _ => ErrorKind::Io,
+    };
+    EvalError::new(kind, format!("`{}` {:?}: {}", name, path, error))
+}
+
+fn file_read(args: &[EvalDataType], filesystem: &FileSystem) -> Result<EvalDataType, EvalError> {
+    expect_arity("file-read", args, 1, 1)?;
+    let path = expect_string("file-read", &args[0])?;
+    fs::read_to_string(filesystem.resolve("file-read", path)?).map(EvalDataType::String).map_err(|error| io_error("file-read", path, error))
+}
+
+fn file_write(name: &str, args: &[EvalDataType], filesystem: &FileSystem, append: bool) -> Result<EvalDataType, EvalError> {
+    expect_arity(name, args, 2, 2)?;
+    let path = expect_string(name, &args[0])?;
+    let text = expect_string(name, &args[1])?;
+    OpenOptions::new().create(true).write(true).append(append).truncate(!append)
+        .open(filesystem.resolve(name, path)?)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map(|_| EvalDataType::Null)
+        .map_err(|error| io_error(name, path, error))
+}
+
+fn file_exists(args: &[EvalDataType], filesystem: &FileSystem) -> Result<EvalDataType, EvalError> {
+    expect_arity("file-exists?", args, 1, 1)?;
+    let path = expect_string("file-exists?", &args[0])?;
+    Ok(EvalDataType::Bool(filesystem.resolve("file-exists?", path)?.exists()))
+}
+
+// `(list-dir [path])`: the sorted names in a directory, the root by default.
+fn list_dir(args: &[EvalDataType], filesystem: &FileSystem) -> Result<EvalDataType, EvalError> {
+    expect_arity("list-dir", args, 0, 1)?;
+    let path = match args.first() {
+        Some(path) => expect_string("list-dir", path)?,
+        None => ".",
+    };
+    let mut names = fs::read_dir(filesystem.resolve("list-dir", path)?)
+        .and_then(|entries| entries.map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned())).collect::<io::Result<Vec<String>>>())
+        .map_err(|error| io_error("list-dir", path, error))?;
+    names.sort();
+    Ok(EvalDataType::List(names.into_iter().map(EvalDataType::String).collect()))
+}
+
+fn delete_file(args: &[EvalDataType], filesystem: &FileSystem) -> Result<EvalDataType, EvalError> {
+    expect_arity("delete-file", args, 1, 1)?;
+    let path = expect_string("delete-file", &args[0])?;
+    fs::remove_file(filesystem.resolve("delete-file", path)?).map(|_| EvalDataType::Null).map_err(|error| io_error("delete-file", path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::capabilities::Capabilities;
+    use crate::eval_error::ErrorKind;
+    use crate::fs_library::FileSystem;
+    use crate::{EvalDataType, Interpreter, InterpreterError};
+
+    fn sandbox(name: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("eva-fs-{}-{}", name, std::process::id()));
+        fs::create_dir_all(root.join("reports")).unwrap();
+        root
+    }
+
+    fn error_kind(value: Result<EvalDataType, InterpreterError>) -> Option<ErrorKind> {
+        match value {
+            Ok(EvalDataType::Error(error)) => Some(error.kind),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_file_built_ins() {
+        let root = sandbox("builtins");
+        let interpreter = Interpreter::new().with_capabilities(Capabilities::none().fs(FileSystem::new(&root).unwrap()));
+        interpreter.eval_str(r#"
+            (file-write "config.json" "{\"name\": \"eva\"}")
+            (file-write "reports/daily.txt" "one")
+            (file-append "reports/daily.txt" ", two")
+        "#).unwrap();
+        assert_eq!(interpreter.eval_str("(get (json-parse (file-read \"config.json\")) \"name\")"), Ok(EvalDataType::from("eva")));
+        assert_eq!(interpreter.eval_str("(file-read \"reports/./daily.txt\")"), Ok(EvalDataType::from("one, two")));
+        assert_eq!(interpreter.eval_str("(list-dir)"), Ok(EvalDataType::from(vec!["config.json", "reports"])));
+        interpreter.eval_str("(delete-file \"config.json\")").unwrap();
+        assert_eq!(interpreter.eval_str("(file-exists? \"config.json\")"), Ok(EvalDataType::Bool(false)));
+        assert_eq!(error_kind(interpreter.eval_str("(file-read \"config.json\")")), Some(ErrorKind::NotFound));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_paths_outside_the_root() {
+        let root = sandbox("traversal");
+        let interpreter = Interpreter::new().with_capabilities(Capabilities::none().fs(FileSystem::new(root.join("reports")).unwrap()));
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+        for path in ["../secret.txt", "/etc/passwd", "a/../../secret.txt"] {
+            assert_eq!(error_kind(interpreter.eval_str(&format!("(file-read \"{}\")", path))), Some(ErrorKind::AccessDenied), "{}", path);
+        }
+        assert_eq!(interpreter.eval_str("(file-exists? \"a/../b\")"), Ok(EvalDataType::Bool(false)));
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("secret.txt"), root.join("reports/link")).unwrap();
+            assert_eq!(error_kind(interpreter.eval_str("(file-read \"link\")")), Some(ErrorKind::AccessDenied));
+            // A dangling link would have `file-write` create its target outside the root.
+            std::os::unix::fs::symlink(root.join("outside.txt"), root.join("reports/dangling")).unwrap();
+            assert_eq!(error_kind(interpreter.eval_str("(file-write \"dangling\" \"escaped\")")), Some(ErrorKind::AccessDenied));
+            assert!(!root.join("outside.txt").exists());
+            fs::create_dir(root.join("reports/inside")).unwrap();
+            std::os::unix::fs::symlink(root.join("reports/inside"), root.join("reports/alias")).unwrap();
+            assert_eq!(interpreter.eval_str("(file-write \"alias/note.txt\" \"kept\")"), Ok(EvalDataType::Null));
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_not_registered_without_capability() {
+        let interpreter = Interpreter::new();
+        assert!(matches!(interpreter.eval_str("(file-read \"config.json\")"), Err(InterpreterError::Runtime(_))));
+    }
+
+    #[test]
+    fn test_bound_names_shadow_built_ins() {
+        let root = sandbox("shadow");
+        let interpreter = Interpreter::new().with_capabilities(Capabilities::none().fs(FileSystem::new(&root).unwrap()));
+        assert_eq!(interpreter.eval_str("(var file-read 5) file-read"), Ok(EvalDataType::Number(5)));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use crate::environment_manager_raw::EnvironmentManagerRaw;
+use crate::printer::print_inline;
+use crate::types::{parse_type, FunctionType, Type};
+use crate::{EvalDataType, EvalType};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub annotation: Option<Type>,
+}
+
+/// The parts of `(def name params [-> type] body…)` or `(lambda params [-> type] body…)` after the name.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Signature {
+    pub params: Vec<Parameter>,
+    pub return_type: Option<Type>,
+    pub body: Vec<EvalType>,
+}
+
+pub struct Function {
+    pub name: String,
+    pub signature: Signature,
+    pub env: Rc<RefCell<EnvironmentManagerRaw>>,
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// The captured environment usually contains the function itself, so it is left out.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function").field("name", &self.name).field("signature", &self.signature).finish()
+    }
+}
+
+impl Signature {
+    pub fn parse(exp: &[EvalType]) -> Result<Signature, String> {
+        let params = match exp.first() {
+            Some(EvalType::Content(params, _)) => params.iter().map(Signature::parse_parameter).collect::<Result<Vec<Parameter>, String>>()?,
+            other => return Err(format!("expected a parameter list, found {:?}", other)),
+        };
+        let (return_type, body) = match &exp[1..] {
+            [EvalType::Value(EvalDataType::String(arrow)), annotation, body @ ..] if arrow == "->" => (Some(parse_type(annotation)?), body),
+            body => (None, body),
+        };
+        if body.is_empty() {
+            return Err("a function needs a body".to_owned());
+        }
+        Ok(Signature { params, return_type, body: body.to_vec() })
+    }
+    // A name, or `(name : type)` as in `var`.
+    pub(crate) fn parse_parameter(exp: &EvalType) -> Result<Parameter, String> {
+        match exp {
+            EvalType::Value(EvalDataType::String(name)) => Ok(Parameter { name: name.clone(), annotation: None }),
+            EvalType::Content(annotated, _) => match annotated.as_slice() {
+                [EvalType::Value(EvalDataType::String(name)), EvalType::Value(EvalDataType::String(colon)), annotation] if colon == ":" => {
+                    Ok(Parameter { name: name.clone(), annotation: Some(parse_type(annotation)?) })
+                }
+                _ => Err(format!("a parameter is a name or (name : type), not {}", print_inline(exp))),
+            },
+            _ => Err(format!("a parameter is a name or (name : type), not {}", print_inline(exp))),
+        }
+    }
+    pub fn function_type(&self) -> FunctionType {
+        FunctionType {
+            params: self.params.iter().map(|param| param.annotation.clone().unwrap_or(Type::Any)).collect(),
+            result: Box::new(self.return_type.clone().unwrap_or(Type::Any)),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::{Rc, Weak};
+use crate::environment_manager_raw::EnvironmentManagerRaw;
+use crate::function::Function;
+use crate::EvalDataType;
+
+type Environment = Rc<RefCell<EnvironmentManagerRaw>>;
+
+// Collections run once this many environments are tracked, and again when the live set has doubled.
+const INITIAL_THRESHOLD: usize = 1024;
+
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct GcStats {
+    /// Environments created since the heap was made.
+    pub allocated: usize,
+    /// Environments freed by collections; ones freed by plain reference counting are not counted.
+    pub collected: usize,
+    /// Environments still alive.
+    pub live: usize,
+    pub collections: usize,
+}
+
+impl From<GcStats> for EvalDataType {
+    fn from(stats: GcStats) -> EvalDataType {
+        EvalDataType::from(BTreeMap::from([
+            ("allocated".to_owned(), stats.allocated),
+            ("collected".to_owned(), stats.collected),
+            ("live".to_owned(), stats.live),
+            ("collections".to_owned(), stats.collections),
+        ]))
+    }
+}
+
+/// Tracks every environment the evaluator creates and collects the ones that only stay alive through cycles,
+/// such as a block environment holding a closure that captured it.
+///
+/// Environments and functions are reference counted, so a collection is a trial deletion: a reference that
+/// does not come from a tracked environment (the Rust stack, the host, an untracked environment) makes its
+/// target a root. Everything reachable from the roots through parents, closures, lists and maps survives;
+/// the rest has its variables cleared, which breaks the cycles and lets reference counting free it.
+pub struct Heap {
+    environments: Vec<Weak<RefCell<EnvironmentManagerRaw>>>,
+    threshold: usize,
+    allocated: usize,
+    collected: usize,
+    collections: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Heap {
+        Heap::new()
+    }
+}
+
+// What a tracked environment refers to: its parent and every function stored in it, including inside lists and maps.
+struct Edges {
+    parent: Option<*const RefCell<EnvironmentManagerRaw>>,
+    functions: Vec<*const Function>,
+}
+
+struct FunctionUse {
+    env: *const RefCell<EnvironmentManagerRaw>,
+    strong: usize,
+    held: usize,
+}
+
+fn collect_functions(value: &EvalDataType, edges: &mut Edges, functions: &mut HashMap<*const Function, FunctionUse>) {
+    match value {
+        EvalDataType::Function(function) => {
+            let key = Rc::as_ptr(function);
+            edges.functions.push(key);
+            functions.entry(key).or_insert(FunctionUse { env: Rc::as_ptr(&function.env), strong: Rc::strong_count(function), held: 0 }).held += 1;
+        }
+        EvalDataType::List(items) => items.iter().for_each(|item| collect_functions(item, edges, functions)),
+        EvalDataType::Map(entries) => entries.values().for_each(|item| collect_functions(item, edges, functions)),
+        _ => {}
+    }
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap { environments: vec![], threshold: INITIAL_THRESHOLD, allocated: 0, collected: 0, collections: 0 }
+    }
+    /// Starts tracking `env`; returns true when enough environments are tracked that a collection is due.
+    pub fn track(&mut self, env: &Environment) -> bool {
+        self.environments.push(Rc::downgrade(env));
+        self.allocated += 1;
+        if self.environments.len() < self.threshold {
+            return false;
+        }
+        self.environments.retain(|env| env.strong_count() > 0);
+        self.environments.len() >= self.threshold
+    }
+    pub fn stats(&self) -> GcStats {
+        GcStats {
+            allocated: self.allocated,
+            collected: self.collected,
+            live: self.environments.iter().filter(|env| env.strong_count() > 0).count(),
+            collections: self.collections,
+        }
+    }
+    /// Frees every tracked environment that is unreachable from outside the tracked environments. A collection is
+    /// skipped while an environment is mutably borrowed, since its contents cannot be traced then.
+    pub fn collect(&mut self) -> GcStats {
+        let environments: Vec<Environment> = self.environments.iter().filter_map(Weak::upgrade).collect();
+        let index: HashMap<*const RefCell<EnvironmentManagerRaw>, usize> = environments.iter().enumerate().map(|(i, env)| (Rc::as_ptr(env), i)).collect();
+        // Strong references not accounted for by tracked environments; `environments` itself holds one.
+        let mut external: Vec<usize> = environments.iter().map(|env| Rc::strong_count(env) - 1).collect();
+        let mut edges: Vec<Edges> = Vec::with_capacity(environments.len());
+        let mut functions: HashMap<*const Function, FunctionUse> = HashMap::new();
+        for env in &environments {
+            let env = match env.try_borrow() {
+                Ok(env) => env,
+                Err(_) => return self.stats(),
+            };
+            let mut env_edges = Edges { parent: env.parent().map(Rc::as_ptr), functions: vec![] };
+            env.env.values().for_each(|value| collect_functions(value, &mut env_edges, &mut functions));
+            if let Some(&parent) = env_edges.parent.as_ref().and_then(|parent| index.get(parent)) {
+                external[parent] -= 1;
+            }
+            edges.push(env_edges);
+        }
+        // A function held only by tracked environments accounts for its reference to the captured environment.
+        for function in functions.values() {
+            if let (true, Some(&env)) = (function.held == function.strong, index.get(&function.env)) {
+                external[env] -= 1;
+            }
+        }
+        let mut marked = vec![false; environments.len()];
+        let mut pending: Vec<usize> = (0..environments.len()).filter(|&i| external[i] > 0).collect();
+        pending.extend(functions.values().filter(|function| function.held < function.strong).filter_map(|function| index.get(&function.env).copied()));
+        while let Some(i) = pending.pop() {
+            if std::mem::replace(&mut marked[i], true) {
+                continue;
+            }
+            let env_edges = &edges[i];
+            pending.extend(env_edges.parent.and_then(|parent| index.get(&parent).copied()));
+            pending.extend(env_edges.functions.iter().filter_map(|function| index.get(&functions[function].env).copied()));
+        }
+        let garbage: Vec<im_rc::HashMap<String, EvalDataType>> = environments.iter().zip(&marked)
+            .filter(|(_, &marked)| !marked)
+            .map(|(env, _)| std::mem::take(&mut env.borrow_mut().env))
+            .collect();
+        let live = environments.len();
+        drop(garbage);
+        drop(environments);
+        self.environments.retain(|env| env.strong_count() > 0);
+        self.collected += live - self.environments.len();
+        self.collections += 1;
+        self.threshold = INITIAL_THRESHOLD.max(self.environments.len() * 2);
+        self.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::environment_manager_raw::EnvironmentManagerRaw;
+    use crate::function::{Function, Signature};
+    use crate::gc::Heap;
+    use crate::parser::parse;
+    use crate::EvalDataType;
+
+    fn closure(env: &Rc<RefCell<EnvironmentManagerRaw>>) -> EvalDataType {
+        let signature = Signature::parse(&parse("(x) x").unwrap()).unwrap();
+        EvalDataType::Function(Rc::new(Function { name: "f".to_owned(), signature, env: Rc::clone(env) }))
+    }
+
+    #[test]
+    fn test_collects_cycles() {
+        let mut heap = Heap::new();
+        let kept = Rc::new(RefCell::new(EnvironmentManagerRaw::new(None, None)));
+        heap.track(&kept);
+        for _ in 0..3 {
+            let env = Rc::new(RefCell::new(EnvironmentManagerRaw::new(None, Some(Rc::clone(&kept)))));
+            heap.track(&env);
+            let f = closure(&env);
+            env.borrow_mut().redefine("f".to_owned(), EvalDataType::List(vec![f]));
+        }
+        let stats = heap.collect();
+        assert_eq!((stats.allocated, stats.collected, stats.live), (4, 3, 1));
+    }
+
+    // What `Interpreter` does for a loop that defines a closure in a block, without the evaluator, which is too slow
+    // in a debug build for a million iterations.
+    #[test]
+    fn test_a_million_closures() {
+        let mut heap = Heap::new();
+        let globals = Rc::new(RefCell::new(EnvironmentManagerRaw::new(None, None)));
+        heap.track(&globals);
+        for _ in 0..1_000_000 {
+            let env = Rc::new(RefCell::new(EnvironmentManagerRaw::new(None, Some(Rc::clone(&globals)))));
+            let signature = Signature { params: vec![], return_type: None, body: vec![] };
+            let f = Function { name: String::new(), signature, env: Rc::clone(&env) };
+            env.borrow_mut().redefine("f".to_owned(), EvalDataType::Function(Rc::new(f)));
+            if heap.track(&env) {
+                heap.collect();
+            }
+        }
+        let stats = heap.stats();
+        assert!(stats.allocated == 1_000_001 && stats.collections > 500 && stats.live <= 2048, "{:?}", stats);
+        assert_eq!(heap.collect().live, 1);
+    }
+
+    #[test]
+    fn test_keeps_externally_held_closures() {
+        let mut heap = Heap::new();
+        let env = Rc::new(RefCell::new(EnvironmentManagerRaw::new(None, None)));
+        heap.track(&env);
+        let f = closure(&env);
+        env.borrow_mut().redefine("f".to_owned(), f.clone());
+        drop(env);
+        assert_eq!(heap.collect().live, 1);
+        drop(f);
+        assert_eq!(heap.collect().live, 0);
+    }
+}
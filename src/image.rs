@@ -0,0 +1,427 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
+use crate::environment_manager_raw::EnvironmentManagerRaw;
+use crate::eval_error::{ErrorKind, EvalError};
+use crate::function::{Function, Parameter, Signature};
+use crate::types::{FunctionType, Type};
+use crate::{EvalDataType, EvalType, Span};
+
+type Environment = Rc<RefCell<EnvironmentManagerRaw>>;
+
+const MAGIC: &[u8; 8] = b"EVAIMAGE";
+/// Bumped whenever the layout changes; images of other versions are rejected rather than misread.
+pub const IMAGE_VERSION: u32 = 1;
+const NO_PARENT: u32 = u32::MAX;
+const ERROR_KINDS: [ErrorKind; 8] = [
+    ErrorKind::IndexOutOfRange,
+    ErrorKind::NotFound,
+    ErrorKind::TypeMismatch,
+    ErrorKind::Arity,
+    ErrorKind::InvalidFormat,
+    ErrorKind::NotSerializable,
+    ErrorKind::Io,
+    ErrorKind::AccessDenied,
+];
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ImageError {
+    Io { path: String, message: String },
+    NotAnImage,
+    UnsupportedVersion(u32),
+    Corrupt(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io { path, message } => write!(f, "{}: {}", path, message),
+            ImageError::NotAnImage => write!(f, "not an eva image"),
+            ImageError::UnsupportedVersion(version) => write!(f, "unsupported image version {} (expected {})", version, IMAGE_VERSION),
+            ImageError::Corrupt(message) => write!(f, "corrupt image: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Encodes `globals` and everything reachable from it: nested values, closures and the environments they
+/// captured, with sharing and cycles preserved.
+///
+/// Layout, little-endian: the magic and version, the environment and function counts, the index of the root
+/// environment, then each environment's parent and constants (parents come first), each function's name,
+/// environment and signature, and finally each environment's bindings.
+pub fn write_image(globals: &Environment) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.visit_env(globals);
+    let root = writer.env_index[&Rc::as_ptr(globals)];
+    writer.out.extend_from_slice(MAGIC);
+    writer.u32(IMAGE_VERSION);
+    writer.u32(writer.envs.len() as u32);
+    writer.u32(writer.functions.len() as u32);
+    writer.u32(root);
+    for env in writer.envs.clone() {
+        let env = env.borrow();
+        let parent = env.parent().map_or(NO_PARENT, |parent| writer.env_index[&Rc::as_ptr(parent)]);
+        writer.u32(parent);
+        let mut constants: Vec<&String> = env.constants().collect();
+        constants.sort();
+        writer.u32(constants.len() as u32);
+        constants.into_iter().for_each(|name| writer.string(name));
+    }
+    for function in writer.functions.clone() {
+        writer.string(&function.name);
+        writer.u32(writer.env_index[&Rc::as_ptr(&function.env)]);
+        let signature = &function.signature;
+        writer.u32(signature.params.len() as u32);
+        for param in &signature.params {
+            writer.string(&param.name);
+            writer.optional_type(param.annotation.as_ref());
+        }
+        writer.optional_type(signature.return_type.as_ref());
+        writer.trees(&signature.body);
+    }
+    for env in writer.envs.clone() {
+        let env = env.borrow();
+        let mut bindings: Vec<(&String, &EvalDataType)> = env.env.iter().collect();
+        bindings.sort_by(|a, b| a.0.cmp(b.0));
+        writer.u32(bindings.len() as u32);
+        for (name, value) in bindings {
+            writer.string(name);
+            writer.value(value);
+        }
+    }
+    writer.out
+}
+
+/// Replaces the bindings of `globals` with the root environment of `image` and rebuilds the rest of it. Returns
+/// the environments that were created, so the caller can hand them to its heap.
+pub fn read_image(image: &[u8], globals: &Environment) -> Result<Vec<Environment>, ImageError> {
+    let mut reader = Reader { bytes: image, position: 0 };
+    if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err(ImageError::NotAnImage);
+    }
+    let version = reader.u32()?;
+    if version != IMAGE_VERSION {
+        return Err(ImageError::UnsupportedVersion(version));
+    }
+    let (env_count, function_count, root) = (reader.u32()? as usize, reader.u32()? as usize, reader.u32()? as usize);
+    if root >= env_count {
+        return Err(reader.corrupt("root environment out of range"));
+    }
+    // Nothing is touched until the whole image has been read, so a bad image leaves the globals as they were. The
+    // counts come from the image, so nothing is allocated for them up front: a corrupt count runs out of bytes.
+    let mut envs: Vec<Environment> = vec![];
+    let mut created = vec![];
+    let mut root_constants = vec![];
+    for index in 0..env_count {
+        let parent = match reader.u32()? {
+            NO_PARENT => None,
+            parent if (parent as usize) < index => Some(Rc::clone(&envs[parent as usize])),
+            _ => return Err(reader.corrupt("environment parent out of order")),
+        };
+        let constants = (0..reader.u32()?).map(|_| reader.string()).collect::<Result<Vec<String>, ImageError>>()?;
+        if index == root {
+            root_constants = constants;
+            envs.push(Rc::clone(globals));
+            continue;
+        }
+        let mut env = EnvironmentManagerRaw::new(None, parent);
+        constants.iter().for_each(|name| env.mark_constant(name));
+        let env = Rc::new(RefCell::new(env));
+        created.push(Rc::clone(&env));
+        envs.push(env);
+    }
+    let mut functions = vec![];
+    for _ in 0..function_count {
+        let name = reader.string()?;
+        let env = reader.index(&envs, "function environment")?;
+        let params = (0..reader.u32()?).map(|_| Ok(Parameter { name: reader.string()?, annotation: reader.optional_type()? }))
+            .collect::<Result<Vec<Parameter>, ImageError>>()?;
+        let return_type = reader.optional_type()?;
+        let body = reader.trees(&[])?;
+        functions.push(Rc::new(Function { name, signature: Signature { params, return_type, body }, env }));
+    }
+    let mut bindings = vec![];
+    for _ in 0..env_count {
+        let count = reader.u32()?;
+        bindings.push((0..count).map(|_| Ok((reader.string()?, reader.value(&functions)?))).collect::<Result<Vec<(String, EvalDataType)>, ImageError>>()?);
+    }
+    if reader.position != image.len() {
+        return Err(reader.corrupt("trailing bytes"));
+    }
+    globals.borrow_mut().clear();
+    root_constants.iter().for_each(|name| globals.borrow_mut().mark_constant(name));
+    for (env, bindings) in envs.iter().zip(bindings) {
+        let mut env = env.borrow_mut();
+        for (name, value) in bindings {
+            env.redefine(name, value);
+        }
+    }
+    Ok(created)
+}
+
+#[derive(Default)]
+struct Writer {
+    out: Vec<u8>,
+    envs: Vec<Environment>,
+    env_index: HashMap<*const RefCell<EnvironmentManagerRaw>, u32>,
+    functions: Vec<Rc<Function>>,
+    function_index: HashMap<*const Function, u32>,
+}
+
+impl Writer {
+    // Parents are numbered before their children, so a reader can create each environment with its parent.
+    fn visit_env(&mut self, env: &Environment) {
+        if self.env_index.contains_key(&Rc::as_ptr(env)) {
+            return;
+        }
+        if let Some(parent) = env.borrow().parent() {
+            self.visit_env(parent);
+        }
+        self.env_index.insert(Rc::as_ptr(env), self.envs.len() as u32);
+        self.envs.push(Rc::clone(env));
+        let values: Vec<EvalDataType> = env.borrow().env.values().cloned().collect();
+        values.iter().for_each(|value| self.visit_value(value));
+    }
+    fn visit_value(&mut self, value: &EvalDataType) {
+        match value {
+            EvalDataType::Function(function) => {
+                if self.function_index.contains_key(&Rc::as_ptr(function)) {
+                    return;
+                }
+                self.function_index.insert(Rc::as_ptr(function), self.functions.len() as u32);
+                self.functions.push(Rc::clone(function));
+                self.visit_env(&function.env);
+            }
+            EvalDataType::List(items) => items.iter().for_each(|item| self.visit_value(item)),
+            EvalDataType::Map(entries) => entries.values().for_each(|item| self.visit_value(item)),
+            _ => {}
+        }
+    }
+    fn u32(&mut self, value: u32) {
+        self.out.extend_from_slice(&value.to_le_bytes());
+    }
+    fn string(&mut self, text: &str) {
+        self.u32(text.len() as u32);
+        self.out.extend_from_slice(text.as_bytes());
+    }
+    fn value(&mut self, value: &EvalDataType) {
+        match value {
+            EvalDataType::String(text) => {
+                self.out.push(0);
+                self.string(text);
+            }
+            EvalDataType::Number(number) => {
+                self.out.push(1);
+                self.out.extend_from_slice(&number.to_le_bytes());
+            }
+            EvalDataType::Bool(value) => self.out.extend_from_slice(&[2, *value as u8]),
+            EvalDataType::List(items) => {
+                self.out.push(3);
+                self.u32(items.len() as u32);
+                items.iter().for_each(|item| self.value(item));
+            }
+            EvalDataType::Error(error) => {
+                self.out.extend_from_slice(&[4, ERROR_KINDS.iter().position(|kind| *kind == error.kind).unwrap_or_default() as u8]);
+                self.string(&error.message);
+            }
+            EvalDataType::Function(function) => {
+                self.out.push(5);
+                self.u32(self.function_index[&Rc::as_ptr(function)]);
+            }
+            EvalDataType::Map(entries) => {
+                self.out.push(6);
+                self.u32(entries.len() as u32);
+                for (key, value) in entries {
+                    self.string(key);
+                    self.value(value);
+                }
+            }
+            EvalDataType::Null => self.out.push(7),
+        }
+    }
+    fn trees(&mut self, trees: &[EvalType]) {
+        self.u32(trees.len() as u32);
+        for tree in trees {
+            match tree {
+                EvalType::Value(value) => {
+                    self.out.push(0);
+                    self.value(value);
+                }
+                EvalType::Content(items, _) => {
+                    self.out.push(1);
+                    self.trees(items);
+                }
+            }
+        }
+    }
+    fn optional_type(&mut self, t: Option<&Type>) {
+        match t {
+            Some(t) => {
+                self.out.push(1);
+                self.type_(t);
+            }
+            None => self.out.push(0),
+        }
+    }
+    fn type_(&mut self, t: &Type) {
+        match t {
+            Type::Any => self.out.push(0),
+            Type::Number => self.out.push(1),
+            Type::String => self.out.push(2),
+            Type::Bool => self.out.push(3),
+            Type::List => self.out.push(4),
+            Type::Error => self.out.push(5),
+            Type::Map => self.out.push(6),
+            Type::Null => self.out.push(7),
+            Type::Function(None) => self.out.push(8),
+            Type::Function(Some(function)) => {
+                self.out.push(9);
+                self.u32(function.params.len() as u32);
+                function.params.iter().for_each(|param| self.type_(param));
+                self.type_(&function.result);
+            }
+            Type::Union(members) => {
+                self.out.push(10);
+                self.u32(members.len() as u32);
+                members.iter().for_each(|member| self.type_(member));
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn corrupt(&self, message: &str) -> ImageError {
+        ImageError::Corrupt(format!("{} at byte {}", message, self.position))
+    }
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ImageError> {
+        match self.bytes.get(self.position..self.position + count) {
+            Some(bytes) => {
+                self.position += count;
+                Ok(bytes)
+            }
+            None => Err(self.corrupt("unexpected end of image")),
+        }
+    }
+    fn u8(&mut self) -> Result<u8, ImageError> {
+        Ok(self.take(1)?[0])
+    }
+    fn u32(&mut self) -> Result<u32, ImageError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap_or_default()))
+    }
+    fn string(&mut self) -> Result<String, ImageError> {
+        let length = self.u32()? as usize;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.corrupt("invalid UTF-8"))
+    }
+    fn index<T>(&mut self, items: &[Rc<T>], what: &str) -> Result<Rc<T>, ImageError> {
+        let index = self.u32()? as usize;
+        items.get(index).cloned().ok_or_else(|| self.corrupt(&format!("{} out of range", what)))
+    }
+    fn value(&mut self, functions: &[Rc<Function>]) -> Result<EvalDataType, ImageError> {
+        Ok(match self.u8()? {
+            0 => EvalDataType::String(self.string()?),
+            1 => EvalDataType::Number(u128::from_le_bytes(self.take(16)?.try_into().unwrap_or_default())),
+            2 => EvalDataType::Bool(self.u8()? != 0),
+            3 => EvalDataType::List((0..self.u32()?).map(|_| self.value(functions)).collect::<Result<Vec<EvalDataType>, ImageError>>()?),
+            4 => {
+                let kind = *ERROR_KINDS.get(self.u8()? as usize).ok_or_else(|| self.corrupt("unknown error kind"))?;
+                EvalDataType::Error(EvalError { kind, message: self.string()? })
+            }
+            5 => EvalDataType::Function(self.index(functions, "function")?),
+            6 => EvalDataType::Map((0..self.u32()?).map(|_| Ok((self.string()?, self.value(functions)?))).collect::<Result<BTreeMap<String, EvalDataType>, ImageError>>()?),
+            7 => EvalDataType::Null,
+            _ => return Err(self.corrupt("unknown value tag")),
+        })
+    }
+    fn trees(&mut self, functions: &[Rc<Function>]) -> Result<Vec<EvalType>, ImageError> {
+        (0..self.u32()?).map(|_| match self.u8()? {
+            0 => Ok(EvalType::Value(self.value(functions)?)),
+            1 => Ok(EvalType::Content(self.trees(functions)?, Span::NONE)),
+            _ => Err(self.corrupt("unknown tree tag")),
+        }).collect()
+    }
+    fn optional_type(&mut self) -> Result<Option<Type>, ImageError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.type_()?)),
+            _ => Err(self.corrupt("unknown type tag")),
+        }
+    }
+    fn type_(&mut self) -> Result<Type, ImageError> {
+        Ok(match self.u8()? {
+            0 => Type::Any,
+            1 => Type::Number,
+            2 => Type::String,
+            3 => Type::Bool,
+            4 => Type::List,
+            5 => Type::Error,
+            6 => Type::Map,
+            7 => Type::Null,
+            8 => Type::Function(None),
+            9 => {
+                let params = (0..self.u32()?).map(|_| self.type_()).collect::<Result<Vec<Type>, ImageError>>()?;
+                Type::Function(Some(FunctionType { params, result: Box::new(self.type_()?) }))
+            }
+            10 => Type::Union((0..self.u32()?).map(|_| self.type_()).collect::<Result<Vec<Type>, ImageError>>()?),
+            _ => return Err(self.corrupt("unknown type tag")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::{ImageError, IMAGE_VERSION};
+    use crate::{EvalDataType, Interpreter};
+
+    #[test]
+    fn test_round_trip() {
+        let interpreter = Interpreter::new();
+        interpreter.eval_str(r#"
+            (const limit 10)
+            (def halve ((n : number)) -> number (if (< n 2) n (halve (/ n 2))))
+            (def counter () (begin ((var count 0) (lambda (step) (set count (+ count step))))))
+            (var next (counter))
+            (var same next)
+            (next 1)
+            (var config (json-parse "{\"name\": \"eva\", \"sizes\": [1, 2]}"))
+            (var failure (to-number "ten"))
+        "#).unwrap();
+        let image = interpreter.write_image();
+        let loaded = Interpreter::new();
+        loaded.eval_str("(var stale 1)").unwrap();
+        loaded.read_image(&image).unwrap();
+        assert_eq!(loaded.get_global("stale"), None);
+        assert_eq!(loaded.eval_str("(halve 100)"), Ok(EvalDataType::Number(1)));
+        // The counter kept its captured environment, and both globals still refer to the one closure.
+        assert_eq!(loaded.eval_str("(next 2) (same 3)"), Ok(EvalDataType::Number(6)));
+        assert_eq!(loaded.get_global("config"), interpreter.get_global("config"));
+        assert_eq!(loaded.get_global("failure"), interpreter.get_global("failure"));
+        assert!(loaded.eval_str("(set limit 1)").is_err());
+        assert_eq!(loaded.write_image().len(), image.len());
+    }
+
+    #[test]
+    fn test_rejects_bad_images() {
+        let interpreter = Interpreter::new();
+        interpreter.eval_str("(var kept 1)").unwrap();
+        let mut image = interpreter.write_image();
+        assert_eq!(interpreter.read_image(b"#!/bin/sh"), Err(ImageError::NotAnImage));
+        assert!(matches!(interpreter.read_image(&image[..image.len() - 1]), Err(ImageError::Corrupt(_))));
+        for counts in [12..16, 16..20] {
+            let mut huge = image.clone();
+            huge[counts].copy_from_slice(&(u32::MAX - 1).to_le_bytes());
+            assert!(matches!(interpreter.read_image(&huge), Err(ImageError::Corrupt(_))));
+        }
+        image[8..12].copy_from_slice(&(IMAGE_VERSION + 1).to_le_bytes());
+        assert_eq!(interpreter.read_image(&image), Err(ImageError::UnsupportedVersion(IMAGE_VERSION + 1)));
+        assert_eq!(interpreter.get_global("kept"), Some(EvalDataType::Number(1)));
+    }
+}
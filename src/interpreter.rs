@@ -0,0 +1,258 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use crate::capabilities::Capabilities;
+use crate::debugger::Debugger;
+use crate::diagnostics::{Diagnostic, Strictness};
+use crate::environment_manager_raw::{EnvironmentManagerRaw, EnvironmentSnapshot};
+use crate::gc::GcStats;
+use crate::image::{self, ImageError};
+use crate::io_library::Io;
+use crate::parser::{parse, ParseError};
+use crate::truthiness::Truthiness;
+use crate::{get_environment_manager, Eva, EvalDataType, EvalType};
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum InterpreterError {
+    Io { path: String, message: String },
+    Parse(ParseError),
+    Runtime(String),
+    NotAFunction(String),
+    /// The debugger was told to quit.
+    Stopped,
+}
+
+impl fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpreterError::Io { path, message } => write!(f, "{}: {}", path, message),
+            InterpreterError::Parse(error) => write!(f, "{}", error),
+            InterpreterError::Runtime(message) => write!(f, "{}", message),
+            InterpreterError::NotAFunction(name) => write!(f, "`{}` is not a function", name),
+            InterpreterError::Stopped => write!(f, "debugging stopped"),
+        }
+    }
+}
+
+impl std::error::Error for InterpreterError {}
+
+/// An evaluator together with the global environment it keeps between calls, for use from a host application.
+pub struct Interpreter {
+    eva: Eva,
+    globals: Rc<RefCell<EnvironmentManagerRaw>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+// Functions defined at the top level capture the globals, so the globals are freed by a last collection.
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        drop(std::mem::replace(&mut self.globals, Rc::new(RefCell::new(EnvironmentManagerRaw::new(None, None)))));
+        self.eva.collect_garbage();
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter::with_strictness(Strictness::Strict)
+    }
+    pub fn with_strictness(strictness: Strictness) -> Interpreter {
+        let interpreter = Interpreter { eva: Eva::with_strictness(strictness), globals: get_environment_manager() };
+        interpreter.eva.track_environment(&interpreter.globals);
+        interpreter
+    }
+    pub fn with_truthiness(mut self, truthiness: Truthiness) -> Interpreter {
+        self.eva.truthiness = truthiness;
+        self
+    }
+    /// Replaces the `Capabilities::standard()` a new interpreter starts with; see `Capabilities`.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Interpreter {
+        self.eva.capabilities = capabilities;
+        self
+    }
+    pub fn with_debugger(mut self, debugger: Debugger) -> Interpreter {
+        self.eva.debugger = Some(RefCell::new(debugger));
+        self
+    }
+    pub fn with_io(mut self, io: Io) -> Interpreter {
+        self.eva.io = RefCell::new(io);
+        self
+    }
+    /// Evaluates every top-level form in order and returns the value of the last one.
+    pub fn eval_str(&self, source: &str) -> Result<EvalDataType, InterpreterError> {
+        self.eval_program(parse(source).map_err(InterpreterError::Parse)?)
+    }
+    /// Like `eval_str`, for a program that was already parsed, and perhaps optimized.
+    pub fn eval_program(&self, program: Vec<EvalType>) -> Result<EvalDataType, InterpreterError> {
+        program.into_iter().try_fold(EvalDataType::Null, |_, exp| self.eva.eval_value(&[exp], Rc::clone(&self.globals)))
+    }
+    pub fn eval_file(&self, path: impl AsRef<Path>) -> Result<EvalDataType, InterpreterError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|error| InterpreterError::Io { path: path.display().to_string(), message: error.to_string() })?;
+        self.eval_str(&source)
+    }
+    /// Defines `name` in the global environment, replacing any previous value.
+    pub fn set_global(&self, name: &str, value: impl Into<EvalDataType>) {
+        self.globals.borrow_mut().redefine(name.to_owned(), value.into());
+    }
+    pub fn get_global(&self, name: &str) -> Option<EvalDataType> {
+        self.globals.borrow().get(name.to_owned())
+    }
+    pub fn call_function(&self, name: &str, args: Vec<EvalDataType>) -> Result<EvalDataType, InterpreterError> {
+        match self.get_global(name) {
+            Some(EvalDataType::Function(function)) => self.eva.call_function(&function, args),
+            _ => Err(InterpreterError::NotAFunction(name.to_owned())),
+        }
+    }
+    /// Captures every global binding in O(1), for a later `restore`.
+    pub fn snapshot(&self) -> EnvironmentSnapshot {
+        self.globals.borrow().snapshot()
+    }
+    /// Rolls the globals back to `snapshot`: bindings made since are removed and assigned ones get their old values.
+    pub fn restore(&self, snapshot: &EnvironmentSnapshot) {
+        self.globals.borrow_mut().restore(snapshot);
+    }
+    /// Encodes the globals, and the closures and environments reachable from them, as a versioned binary image.
+    pub fn write_image(&self) -> Vec<u8> {
+        image::write_image(&self.globals)
+    }
+    /// Replaces the globals with the ones stored in `image`; on error they are left untouched.
+    pub fn read_image(&self, image: &[u8]) -> Result<(), ImageError> {
+        for env in image::read_image(image, &self.globals)? {
+            self.eva.track_environment(&env);
+        }
+        Ok(())
+    }
+    pub fn save_image(&self, path: impl AsRef<Path>) -> Result<(), ImageError> {
+        let path = path.as_ref();
+        fs::write(path, self.write_image()).map_err(|error| ImageError::Io { path: path.display().to_string(), message: error.to_string() })
+    }
+    pub fn load_image(&self, path: impl AsRef<Path>) -> Result<(), ImageError> {
+        let path = path.as_ref();
+        let image = fs::read(path).map_err(|error| ImageError::Io { path: path.display().to_string(), message: error.to_string() })?;
+        self.read_image(&image)
+    }
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.eva.diagnostics()
+    }
+    pub fn collect_garbage(&self) -> GcStats {
+        self.eva.collect_garbage()
+    }
+    pub fn gc_stats(&self) -> GcStats {
+        self.eva.gc_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::{EvalDataType, Interpreter, InterpreterError};
+
+    #[test]
+    fn test_eval_str_keeps_globals() {
+        let interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_str("(var total (* 6 7))"), Ok(EvalDataType::Number(42)));
+        assert_eq!(interpreter.eval_str("(+ total 1)"), Ok(EvalDataType::Number(43)));
+        assert_eq!(interpreter.get_global("VERSION"), Some(EvalDataType::from("1.0.0")));
+    }
+
+    #[test]
+    fn test_globals_from_host() {
+        let interpreter = Interpreter::new();
+        interpreter.set_global("width", 4u64);
+        interpreter.set_global("name", "eva");
+        assert_eq!(interpreter.eval_str("(format \"{} is {}\" name width)"), Ok(EvalDataType::from("eva is 4")));
+        assert_eq!(u64::try_from(interpreter.get_global("width").unwrap()), Ok(4));
+    }
+
+    #[test]
+    fn test_call_function() {
+        let interpreter = Interpreter::new();
+        interpreter.eval_str("(def area ((w : number) (h : number)) -> number (* w h))").unwrap();
+        assert_eq!(interpreter.call_function("area", vec![3u64.into(), 5u64.into()]), Ok(EvalDataType::Number(15)));
+        assert_eq!(interpreter.call_function("VERSION", vec![]), Err(InterpreterError::NotAFunction("VERSION".to_owned())));
+        assert_eq!(interpreter.call_function("area", vec![true.into(), 5u64.into()]),
+            Err(InterpreterError::Runtime("argument `w` of `area` expects number, got true".to_owned())));
+    }
+
+    #[test]
+    fn test_errors() {
+        let interpreter = Interpreter::new();
+        assert!(matches!(interpreter.eval_str("(+ 1"), Err(InterpreterError::Parse(_))));
+        assert_eq!(interpreter.eval_str("(set missing 1)"), Err(InterpreterError::Runtime("assignment to undeclared variable `missing`".to_owned())));
+        assert!(matches!(interpreter.eval_file("/nonexistent/program.eva"), Err(InterpreterError::Io { .. })));
+        // Malformed forms and arithmetic faults are errors too, and the interpreter stays usable after them.
+        assert_eq!(interpreter.eval_str("(/ 1 0)"), Err(InterpreterError::Runtime("division by zero in `(/ 1 0)`".to_owned())));
+        assert_eq!(interpreter.eval_str("(* 340282366920938463463374607431768211455 2)"),
+            Err(InterpreterError::Runtime("`(* 340282366920938463463374607431768211455 2)` overflows".to_owned())));
+        assert_eq!(interpreter.eval_str("(+)"), Err(InterpreterError::Runtime("`+` expects two arguments, got 0".to_owned())));
+        assert_eq!(interpreter.eval_str("()"), Err(InterpreterError::Runtime("cannot evaluate the empty form ()".to_owned())));
+        assert_eq!(interpreter.eval_str("(+ 1 2)"), Ok(EvalDataType::Number(3)));
+    }
+
+    #[test]
+    fn test_eval_file() {
+        let path = std::env::temp_dir().join(format!("eva-interpreter-{}.eva", std::process::id()));
+        fs::write(&path, "(def double (x) (+ x x))\n(double 21)\n").unwrap();
+        let interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_file(&path), Ok(EvalDataType::Number(42)));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let interpreter = Interpreter::new();
+        interpreter.eval_str("(var total 1) (def add (x) (set total (+ total x)))").unwrap();
+        let snapshot = interpreter.snapshot();
+        interpreter.eval_str("(add 10) (var scratch 5) (const limit 3)").unwrap();
+        assert_eq!(interpreter.get_global("total"), Some(EvalDataType::Number(11)));
+        interpreter.restore(&snapshot);
+        assert_eq!(interpreter.get_global("total"), Some(EvalDataType::Number(1)));
+        assert_eq!(interpreter.get_global("scratch"), None);
+        // `limit` is no longer a constant, so it can be declared again.
+        assert_eq!(interpreter.eval_str("(var limit 4) (add limit)"), Ok(EvalDataType::Number(5)));
+        // A snapshot can be restored any number of times.
+        interpreter.restore(&snapshot);
+        assert_eq!(interpreter.eval_str("(add 2)"), Ok(EvalDataType::Number(3)));
+    }
+
+    fn run_closure_loop(iterations: u128) -> Interpreter {
+        let interpreter = Interpreter::new();
+        // Every block holds a closure that captured the block, so reference counting alone never frees it.
+        let source = format!("(var i 0) (while ((< i {}) (begin ((def f (x) (+ x i)) (set i (+ i 1)))))) i", iterations);
+        assert_eq!(interpreter.eval_str(&source), Ok(EvalDataType::Number(iterations)));
+        let stats = interpreter.gc_stats();
+        assert!(stats.allocated as u128 > iterations && stats.collections > 0, "{:?}", stats);
+        assert!(stats.live <= 2048, "{:?}", stats);
+        interpreter
+    }
+
+    #[test]
+    fn test_closure_cycles_are_collected() {
+        let interpreter = run_closure_loop(100_000);
+        assert_eq!(interpreter.collect_garbage().live, 1);
+        assert_eq!(interpreter.eval_str("(get (gc) \"live\")"), Ok(EvalDataType::Number(1)));
+    }
+
+    #[test]
+    fn test_drop_frees_globals() {
+        let interpreter = Interpreter::new();
+        interpreter.eval_str("(def count-down (n) (if (< n 1) n (count-down (/ n 2))))").unwrap();
+        let globals = std::rc::Rc::downgrade(&interpreter.globals);
+        drop(interpreter);
+        assert!(globals.upgrade().is_none());
+    }
+
+    // Takes a while in a debug build; run with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_a_million_evaluated_closures() {
+        run_closure_loop(1_000_000);
+    }
+}
@@ -0,0 +1,127 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::rc::Rc;
+use crate::eval_error::{ErrorKind, EvalError};
+use crate::string_library::{expect_arity, to_text};
+use crate::EvalDataType;
+
+pub const IO_BUILTINS: [&str; 4] = ["print", "println", "eprint", "read-line"];
+
+pub fn is_io_builtin(name: &str) -> bool {
+    IO_BUILTINS.contains(&name)
+}
+
+/// Where `print`, `println`, `eprint` and `read-line` write and read; the process's standard streams by default.
+pub struct Io {
+    pub stdout: Box<dyn Write>,
+    pub stderr: Box<dyn Write>,
+    pub stdin: Box<dyn BufRead>,
+}
+
+impl Default for Io {
+    fn default() -> Io {
+        Io::standard()
+    }
+}
+
+impl Io {
+    pub fn standard() -> Io {
+        Io { stdout: Box::new(io::stdout()), stderr: Box::new(io::stderr()), stdin: Box::new(BufReader::new(io::stdin())) }
+    }
+    pub fn with_stdout(self, stdout: impl Write + 'static) -> Io {
+        Io { stdout: Box::new(stdout), ..self }
+    }
+    pub fn with_stderr(self, stderr: impl Write + 'static) -> Io {
+        Io { stderr: Box::new(stderr), ..self }
+    }
+    pub fn with_stdin(self, stdin: impl BufRead + 'static) -> Io {
+        Io { stdin: Box::new(stdin), ..self }
+    }
+}
+
+/// A sink that keeps what is written to it, for capturing a script's output. Clones share the same buffer.
+#[derive(Clone, Default)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    pub fn new() -> Capture {
+        Capture::default()
+    }
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// `eprint` ends its output with a newline, so diagnostics from a script never run together.
+pub fn call(name: &str, args: &[EvalDataType], io: &mut Io) -> EvalDataType {
+    let result = match name {
+        "print" => write(&mut io.stdout, args, ""),
+        "println" => write(&mut io.stdout, args, "\n"),
+        "eprint" => write(&mut io.stderr, args, "\n"),
+        "read-line" => read_line(args, &mut io.stdin),
+        _ => Err(EvalError::new(ErrorKind::NotFound, format!("`{}` is not an I/O built-in", name))),
+    };
+    result.unwrap_or_else(EvalDataType::Error)
+}
+
+// Arguments are separated by spaces, and strings are written without quotes.
+fn write(sink: &mut Box<dyn Write>, args: &[EvalDataType], end: &str) -> Result<EvalDataType, EvalError> {
+    let text: Vec<String> = args.iter().map(to_text).collect();
+    sink.write_all(format!("{}{}", text.join(" "), end).as_bytes())
+        .and_then(|_| sink.flush())
+        .map(|_| EvalDataType::Null)
+        .map_err(|error| EvalError::new(ErrorKind::Io, error.to_string()))
+}
+
+// The line without its line ending, or `null` at the end of the input.
+fn read_line(args: &[EvalDataType], stdin: &mut Box<dyn BufRead>) -> Result<EvalDataType, EvalError> {
+    expect_arity("read-line", args, 0, 0)?;
+    let mut line = String::new();
+    match stdin.read_line(&mut line) {
+        Ok(0) => Ok(EvalDataType::Null),
+        Ok(_) => Ok(EvalDataType::String(line.trim_end_matches(['\n', '\r']).to_owned())),
+        Err(error) => Err(EvalError::new(ErrorKind::Io, error.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io_library::{Capture, Io};
+    use crate::{EvalDataType, Interpreter};
+
+    #[test]
+    fn test_print_and_read_line() {
+        let (stdout, stderr) = (Capture::new(), Capture::new());
+        let io = Io::standard().with_stdout(stdout.clone()).with_stderr(stderr.clone()).with_stdin("eva\r\nsecond\n".as_bytes());
+        let interpreter = Interpreter::new().with_io(io);
+        interpreter.eval_str(r#"
+            (var name (read-line))
+            (print "name: ")
+            (println name (split "a,b" ",") 42 true)
+            (eprint (format "hello {}" name))
+        "#).unwrap();
+        assert_eq!(stdout.text(), "name: eva (\"a\" \"b\") 42 true\n");
+        assert_eq!(stderr.text(), "hello eva\n");
+        assert_eq!(interpreter.eval_str("(read-line)"), Ok(EvalDataType::from("second")));
+        assert_eq!(interpreter.eval_str("(read-line)"), Ok(EvalDataType::Null));
+        assert!(matches!(interpreter.eval_str("(read-line 1)"), Ok(EvalDataType::Error(_))));
+    }
+
+    #[test]
+    fn test_bound_names_shadow_built_ins() {
+        let stdout = Capture::new();
+        let interpreter = Interpreter::new().with_io(Io::standard().with_stdout(stdout.clone()));
+        assert_eq!(interpreter.eval_str("(var println 2) println"), Ok(EvalDataType::Number(2)));
+        assert_eq!(stdout.text(), "");
+    }
+}
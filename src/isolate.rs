@@ -0,0 +1,242 @@
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use crate::Interpreter;
+
+type Job = Box<dyn FnOnce(&Interpreter) + Send>;
+type Create = Box<dyn FnOnce() -> Interpreter + Send>;
+
+/// An isolate did not start because building its interpreter panicked with `message`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SpawnError {
+    pub message: String,
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "building the isolate's interpreter failed: {}", self.message)
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
+/// An `Interpreter` living on a thread of its own. Values and environments never leave that thread: work is sent
+/// to it as a closure and only the closure's result comes back, so the handle is `Send + Sync` and can be shared
+/// by the workers of a thread pool.
+pub struct Isolate {
+    workers: Workers,
+}
+
+impl Default for Isolate {
+    fn default() -> Isolate {
+        Isolate::spawn()
+    }
+}
+
+impl Isolate {
+    pub fn spawn() -> Isolate {
+        Isolate::spawn_with(Interpreter::new).expect("a new interpreter is always built")
+    }
+    /// Starts an isolate whose interpreter is built by `create` on the isolate's thread, so it can be given its
+    /// own capabilities and io, or define shared functions before taking jobs. Waits until `create` is done, and
+    /// fails if it panicked.
+    pub fn spawn_with(create: impl FnOnce() -> Interpreter + Send + 'static) -> Result<Isolate, SpawnError> {
+        Ok(Isolate { workers: Workers::spawn(vec![Box::new(create)])? })
+    }
+    /// Runs `job` on the isolate's thread and waits for its result. Jobs run one at a time in the order they
+    /// arrive; a panic in `job` is resumed on the calling thread and leaves the isolate running.
+    pub fn run<T: Send + 'static>(&self, job: impl FnOnce(&Interpreter) -> T + Send + 'static) -> T {
+        self.workers.run(job)
+    }
+}
+
+/// A fixed set of isolates taking jobs from one queue, so scripts from many threads run in parallel and a slow job
+/// only holds up the isolate running it.
+pub struct IsolatePool {
+    workers: Workers,
+}
+
+impl IsolatePool {
+    pub fn new(size: usize) -> IsolatePool {
+        IsolatePool::spawn_with(size, Interpreter::new).expect("a new interpreter is always built")
+    }
+    /// Every isolate builds its interpreter with `create`, since globals are not shared between isolates. Fails,
+    /// stopping the isolates already started, if any of them panicked in `create`.
+    pub fn spawn_with(size: usize, create: impl Fn() -> Interpreter + Send + Sync + 'static) -> Result<IsolatePool, SpawnError> {
+        assert!(size > 0, "an isolate pool needs at least one isolate");
+        let create = Arc::new(create);
+        let creates = (0..size).map(|_| {
+            let create = Arc::clone(&create);
+            Box::new(move || create()) as Create
+        }).collect();
+        Ok(IsolatePool { workers: Workers::spawn(creates)? })
+    }
+    /// Runs `job` on the first isolate that is free; see `Isolate::run`.
+    pub fn run<T: Send + 'static>(&self, job: impl FnOnce(&Interpreter) -> T + Send + 'static) -> T {
+        self.workers.run(job)
+    }
+    pub fn size(&self) -> usize {
+        self.workers.threads.len()
+    }
+}
+
+// Threads that each own an interpreter and take jobs from one shared queue, whichever is free first.
+struct Workers {
+    jobs: Mutex<Option<Sender<Job>>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Workers {
+    // Waits for every interpreter to be built, so a failing `create` is reported here and not by a later job.
+    fn spawn(creates: Vec<Create>) -> Result<Workers, SpawnError> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(receiver));
+        let (ready, started) = mpsc::channel();
+        let threads = creates.into_iter().map(|create| {
+            let (queue, ready) = (Arc::clone(&queue), ready.clone());
+            thread::spawn(move || {
+                let interpreter = match panic::catch_unwind(AssertUnwindSafe(create)) {
+                    Ok(interpreter) => interpreter,
+                    Err(payload) => {
+                        let _ = ready.send(Err(panic_message(payload)));
+                        return;
+                    }
+                };
+                let _ = ready.send(Ok(()));
+                drop(ready);
+                loop {
+                    // The queue is only locked while waiting for a job, not while running it.
+                    let job = queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv();
+                    match job {
+                        Ok(job) => job(&interpreter),
+                        Err(_) => break,
+                    }
+                }
+            })
+        }).collect();
+        drop(ready);
+        // On failure the workers are dropped, which stops the ones that did start.
+        let workers = Workers { jobs: Mutex::new(Some(sender)), threads };
+        match started.iter().find_map(Result::err) {
+            Some(message) => Err(SpawnError { message }),
+            None => Ok(workers),
+        }
+    }
+    fn run<T: Send + 'static>(&self, job: impl FnOnce(&Interpreter) -> T + Send + 'static) -> T {
+        let (sender, receiver) = mpsc::channel();
+        let job: Job = Box::new(move |interpreter| {
+            let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(|| job(interpreter))));
+        });
+        let jobs = self.jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        jobs.as_ref().expect("isolate is shut down").send(job).expect("isolate thread stopped");
+        drop(jobs);
+        match receiver.recv().expect("isolate thread stopped") {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match (payload.downcast_ref::<String>(), payload.downcast_ref::<&str>()) {
+        (Some(message), _) => message.clone(),
+        (None, Some(message)) => message.to_string(),
+        (None, None) => "panicked".to_owned(),
+    }
+}
+
+// Closing the queue ends every worker loop once queued jobs are done.
+impl Drop for Workers {
+    fn drop(&mut self) {
+        self.jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use crate::capabilities::Capabilities;
+    use crate::isolate::{Isolate, IsolatePool, SpawnError};
+    use crate::{Interpreter, InterpreterError};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_isolate_keeps_state() {
+        assert_send_sync::<Isolate>();
+        assert_send_sync::<IsolatePool>();
+        let isolate = Isolate::spawn_with(|| {
+            let interpreter = Interpreter::new();
+            interpreter.eval_str("(var calls 0)").unwrap();
+            interpreter
+        }).unwrap();
+        isolate.run(|interpreter| interpreter.eval_str("(set calls (+ calls 1))").map(|_| ())).unwrap();
+        let calls = isolate.run(|interpreter| interpreter.eval_str("calls").map(|value| u64::try_from(value).ok()));
+        assert_eq!(calls, Ok(Some(1)));
+        let error = isolate.run(|interpreter| interpreter.eval_str("(set missing 1)").map(|value| value.to_string()));
+        assert_eq!(error, Err(InterpreterError::Runtime("assignment to undeclared variable `missing`".to_owned())));
+    }
+
+    #[test]
+    fn test_isolate_capabilities() {
+        let has_clock = |isolate: &Isolate| isolate.run(|interpreter| interpreter.eval_str("(now)").is_ok());
+        assert!(!has_clock(&Isolate::spawn()));
+        assert!(has_clock(&Isolate::spawn_with(|| Interpreter::new().with_capabilities(Capabilities::none().clock())).unwrap()));
+    }
+
+    #[test]
+    fn test_panicking_job_leaves_isolate_running() {
+        let isolate = Arc::new(Isolate::spawn());
+        let failed = thread::spawn({
+            let isolate = Arc::clone(&isolate);
+            move || isolate.run(|_| panic!("job failed"))
+        }).join();
+        assert!(failed.is_err());
+        assert_eq!(isolate.run(|interpreter| interpreter.eval_str("(* 6 7)").map(|value| value.to_string())), Ok("42".to_owned()));
+    }
+
+    #[test]
+    fn test_failing_constructor_is_reported() {
+        let error = SpawnError { message: "no interpreter today".to_owned() };
+        let create = || -> Interpreter { panic!("no interpreter today") };
+        assert_eq!(Isolate::spawn_with(create).err(), Some(error.clone()));
+        assert_eq!(IsolatePool::spawn_with(2, create).err(), Some(error));
+    }
+
+    #[test]
+    fn test_pool_shares_one_queue() {
+        let pool = IsolatePool::new(2);
+        let (release, released) = mpsc::channel::<()>();
+        let slow = thread::scope(|scope| {
+            let slow = scope.spawn(|| pool.run(move |_| released.recv().is_ok()));
+            // Every job goes to the isolate that is free, never behind the one waiting for `release`.
+            let quick: Vec<String> = (0..4).map(|n| pool.run(move |interpreter| interpreter.eval_str(&format!("(* {} 2)", n)).unwrap().to_string())).collect();
+            assert_eq!(quick, vec!["0", "2", "4", "6"]);
+            release.send(()).unwrap();
+            slow.join().unwrap()
+        });
+        assert!(slow);
+    }
+
+    #[test]
+    fn test_pool_across_threads() {
+        let pool = Arc::new(IsolatePool::spawn_with(3, || {
+            let interpreter = Interpreter::new();
+            interpreter.eval_str("(def square (x) (* x x))").unwrap();
+            interpreter
+        }).unwrap());
+        let workers: Vec<thread::JoinHandle<u64>> = (1..=8u64).map(|n| {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || pool.run(move |interpreter| u64::try_from(interpreter.eval_str(&format!("(square {})", n)).unwrap()).unwrap()))
+        }).collect();
+        let squares: Vec<u64> = workers.into_iter().map(|worker| worker.join().unwrap()).collect();
+        assert_eq!(squares, vec![1, 4, 9, 16, 25, 36, 49, 64]);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::eval_error::{ErrorKind, EvalError};
+use crate::string_library::{expect_arity, expect_index, expect_string};
+use crate::EvalDataType;
+
+pub const JSON_BUILTINS: [&str; 4] = ["json-parse", "json-stringify", "get", "keys"];
+
+pub fn is_json_builtin(name: &str) -> bool {
+    JSON_BUILTINS.contains(&name)
+}
+
+pub fn call(name: &str, args: &[EvalDataType]) -> EvalDataType {
+    let result = match name {
+        "json-parse" => json_parse(args),
+        "json-stringify" => json_stringify(args),
+        "get" => get(args),
+        "keys" => keys(args),
+        _ => Err(EvalError::new(ErrorKind::NotFound, format!("`{}` is not a JSON built-in", name))),
+    };
+    result.unwrap_or_else(EvalDataType::Error)
+}
+
+fn json_parse(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("json-parse", args, 1, 1)?;
+    serde_json::from_str(expect_string("json-parse", &args[0])?)
+        .map_err(|error| EvalError::new(ErrorKind::InvalidFormat, format!("`json-parse`: {}", error)))
+}
+
+fn json_stringify(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("json-stringify", args, 1, 1)?;
+    serde_json::to_string(&args[0])
+        .map(EvalDataType::String)
+        .map_err(|error| EvalError::new(ErrorKind::NotSerializable, format!("`json-stringify`: {}", error)))
+}
+
+// `(get map key)` or `(get list index)`, so parsed documents can be taken apart.
+fn get(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("get", args, 2, 2)?;
+    match &args[0] {
+        EvalDataType::Map(entries) => {
+            let key = expect_string("get", &args[1])?;
+            entries.get(key).cloned().ok_or_else(|| EvalError::new(ErrorKind::NotFound, format!("`get`: no key {:?}", key)))
+        }
+        EvalDataType::List(items) => {
+            let index = expect_index("get", &args[1])?;
+            items.get(index).cloned().ok_or_else(|| EvalError::new(ErrorKind::IndexOutOfRange, format!("`get` index {} is out of range for {} items", index, items.len())))
+        }
+        other => Err(EvalError::new(ErrorKind::TypeMismatch, format!("`get` expects a map or a list, got {:?}", other))),
+    }
+}
+
+fn keys(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("keys", args, 1, 1)?;
+    match &args[0] {
+        EvalDataType::Map(entries) => Ok(EvalDataType::List(entries.keys().cloned().map(EvalDataType::String).collect())),
+        other => Err(EvalError::new(ErrorKind::TypeMismatch, format!("`keys` expects a map, got {:?}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::eval_error::ErrorKind;
+    use crate::function::{Function, Signature};
+    use crate::json_library::call;
+    use crate::parser::parse;
+    use crate::{get_environment_manager, EvalDataType};
+
+    fn text(value: &str) -> EvalDataType {
+        EvalDataType::String(value.to_owned())
+    }
+
+    fn error_kind(value: EvalDataType) -> ErrorKind {
+        match value {
+            EvalDataType::Error(error) => error.kind,
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_and_access() {
+        let document = call("json-parse", &[text(r#"{"user": {"name": "eva", "roles": ["admin", "dev"]}, "active": true}"#)]);
+        let user = call("get", &[document.clone(), text("user")]);
+        assert_eq!(call("get", &[call("get", &[user.clone(), text("roles")]), EvalDataType::Number(1)]), text("dev"));
+        assert_eq!(call("keys", &[document]), EvalDataType::List(vec![text("active"), text("user")]));
+        assert_eq!(error_kind(call("get", &[user, text("email")])), ErrorKind::NotFound);
+        assert_eq!(error_kind(call("json-parse", &[text("{\"open\": ")])), ErrorKind::InvalidFormat);
+    }
+
+    #[test]
+    fn test_stringify() {
+        let value = EvalDataType::List(vec![text("a \"quoted\" word"), EvalDataType::Number(3), EvalDataType::Null]);
+        assert_eq!(call("json-stringify", &[value]), text(r#"["a \"quoted\" word",3,null]"#));
+        let function = EvalDataType::Function(Rc::new(Function {
+            name: "square".to_owned(),
+            signature: Signature::parse(&parse("(x) (* x x)").unwrap()).unwrap(),
+            env: get_environment_manager(),
+        }));
+        match call("json-stringify", &[EvalDataType::List(vec![function])]) {
+            EvalDataType::Error(error) => {
+                assert_eq!(error.kind, ErrorKind::NotSerializable);
+                assert_eq!(error.message, "`json-stringify`: cannot serialize function `square`");
+            }
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bound_names_shadow_built_ins() {
+        let interpreter = crate::Interpreter::new();
+        assert_eq!(interpreter.eval_str("(var keys 5) keys"), Ok(EvalDataType::Number(5)));
+        assert_eq!(interpreter.eval_str("(def get (x) (+ x 1)) (get 1)"), Ok(EvalDataType::Number(2)));
+    }
+}
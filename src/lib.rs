@@ -0,0 +1,1311 @@
+
+extern crate core;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use regex::{Regex};
+use crate::capabilities::Capabilities;
+use crate::debugger::Debugger;
+use crate::diagnostics::{Diagnostic, Strictness};
+use crate::environment_manager_raw::EnvironmentManagerRaw;
+use crate::eval_error::EvalError;
+use crate::function::{Function, Signature};
+use crate::gc::{GcStats, Heap};
+use crate::io_library::Io;
+use crate::truthiness::Truthiness;
+use crate::pattern::{clause_parts, Pattern, VarTarget};
+use crate::printer::print_inline;
+use crate::system_library::Random;
+
+
+mod conversions;
+pub mod capabilities;
+pub mod dap;
+pub mod debugger;
+pub mod diagnostics;
+pub mod environment_manager_raw;
+pub mod eval_error;
+pub mod formatter;
+pub mod fs_library;
+pub mod function;
+pub mod gc;
+pub mod image;
+pub mod io_library;
+mod interpreter;
+#[cfg(feature = "threads")]
+pub mod isolate;
+pub mod json_library;
+pub mod lint;
+pub mod lsp;
+pub mod optimizer;
+pub mod parser;
+pub mod pattern;
+pub mod printer;
+pub mod repl;
+mod serialization;
+pub mod runner;
+pub mod string_library;
+pub mod system_library;
+pub mod truthiness;
+pub mod type_checker;
+pub mod type_library;
+pub mod types;
+
+pub use crate::conversions::ConversionError;
+pub use crate::interpreter::{Interpreter, InterpreterError};
+
+
+#[derive(Clone)]
+pub enum EvalType {
+    Content(Vec<EvalType>, Span),
+    Value(EvalDataType),
+}
+
+/// Where a list was written: the line and column of its `(`. Only the parser knows them, for the debugger; lists
+/// built any other way have `Span::NONE`. Spans take no part in comparing or printing trees.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub const NONE: Span = Span { line: 0, column: 0 };
+    pub fn new(line: usize, column: usize) -> Span {
+        Span { line, column }
+    }
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+}
+
+impl PartialEq for EvalType {
+    fn eq(&self, other: &EvalType) -> bool {
+        match (self, other) {
+            (EvalType::Content(content, _), EvalType::Content(other, _)) => content == other,
+            (EvalType::Value(value), EvalType::Value(other)) => value == other,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for EvalType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalType::Content(content, _) => f.debug_tuple("Content").field(content).finish(),
+            EvalType::Value(value) => f.debug_tuple("Value").field(value).finish(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum EvalDataType {
+    String(String),
+    Number(u128),
+    Bool(bool),
+    List(Vec<EvalDataType>),
+    Error(EvalError),
+    Function(Rc<Function>),
+    Map(BTreeMap<String, EvalDataType>),
+    Null,
+}
+
+/// What evaluating gives: a value, or the runtime error or debugger stop that abandoned the evaluation.
+pub type EvalResult<T = EvalType> = Result<T, InterpreterError>;
+
+fn runtime_error<T>(message: String) -> EvalResult<T> {
+    Err(InterpreterError::Runtime(message))
+}
+
+/// A `(name value…)` pair of a `let`, `let*` or `letrec` form.
+pub type LetBinding<'a> = (&'a str, &'a [EvalType]);
+
+pub struct Eva {
+    strictness: Strictness,
+    truthiness: Truthiness,
+    diagnostics: RefCell<Vec<Diagnostic>>,
+    heap: RefCell<Heap>,
+    io: RefCell<Io>,
+    capabilities: Capabilities,
+    random: Random,
+    debugger: Option<RefCell<Debugger>>,
+}
+
+impl Default for Eva {
+    fn default() -> Eva {
+        Eva::new()
+    }
+}
+
+impl Eva {
+    pub fn new() -> Eva {
+        Eva::with_strictness(Strictness::Strict)
+    }
+    pub fn with_strictness(strictness: Strictness) -> Eva {
+        Eva {
+            strictness,
+            truthiness: Truthiness::Strict,
+            diagnostics: RefCell::new(vec![]),
+            heap: RefCell::new(Heap::new()),
+            io: RefCell::new(Io::standard()),
+            capabilities: Capabilities::standard(),
+            random: Random::new(),
+            debugger: None,
+        }
+    }
+    pub fn with_truthiness(mut self, truthiness: Truthiness) -> Eva {
+        self.truthiness = truthiness;
+        self
+    }
+    /// Sends the output of `print`, `println` and `eprint`, and the input of `read-line`, to `io`.
+    pub fn with_io(mut self, io: Io) -> Eva {
+        self.io = RefCell::new(io);
+        self
+    }
+    /// Replaces the `Capabilities::standard()` a new evaluator starts with.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Eva {
+        self.capabilities = capabilities;
+        self
+    }
+    /// Evaluates under `debugger`, which may pause before every form.
+    pub fn with_debugger(mut self, debugger: Debugger) -> Eva {
+        self.debugger = Some(RefCell::new(debugger));
+        self
+    }
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics.borrow().clone()
+    }
+    /// Has the garbage collector track `env`, e.g. the globals of a host, which the evaluator did not create.
+    pub fn track_environment(&self, env: &Rc<RefCell<EnvironmentManagerRaw>>) {
+        self.heap.borrow_mut().track(env);
+    }
+    pub fn collect_garbage(&self) -> GcStats {
+        self.heap.borrow_mut().collect()
+    }
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.borrow().stats()
+    }
+    // Every environment the evaluator creates goes through here, so it is tracked and may trigger a collection.
+    fn new_environment(&self, parent: &Rc<RefCell<EnvironmentManagerRaw>>) -> Rc<RefCell<EnvironmentManagerRaw>> {
+        let env = Rc::new(RefCell::new(EnvironmentManagerRaw::new(None, Some(Rc::clone(parent)))));
+        if self.heap.borrow_mut().track(&env) {
+            self.collect_garbage();
+        }
+        env
+    }
+}
+
+impl Eva {
+    pub fn eval(&self, exp: Vec<EvalType>, env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match exp.first() {
+            Some(EvalType::Content(v, span)) if self.debugger.is_some() => self.eval_debugged(v, *span, env_manager),
+            Some(EvalType::Content(v, _)) => self.eval(v.clone(), env_manager),
+            Some(EvalType::Value(v)) => self.evaluate_eval_data(v, &exp[1..], env_manager),
+            None => runtime_error("cannot evaluate the empty form ()".to_owned()),
+        }
+    }
+    fn eval_debugged(&self, form: &[EvalType], span: Span, env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        if let Some(debugger) = &self.debugger {
+            debugger.borrow_mut().enter_form(form, span, &env_manager)?;
+        }
+        let result = self.eval(form.to_vec(), env_manager);
+        if let Some(debugger) = &self.debugger {
+            debugger.borrow_mut().leave_form();
+        }
+        result
+    }
+    pub fn evaluate_eval_data(&self, eval_data_type: &EvalDataType, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match eval_data_type {
+            EvalDataType::String(v) => self.process_operation(v, exp, env_manager),
+            EvalDataType::Number(v) => Ok(EvalType::Value(EvalDataType::Number(*v))),
+            EvalDataType::Bool(v) => Ok(EvalType::Value(EvalDataType::Bool(*v))),
+            EvalDataType::List(_) | EvalDataType::Error(_) | EvalDataType::Function(_) | EvalDataType::Map(_) | EvalDataType::Null => Ok(EvalType::Value(eval_data_type.clone())),
+        }
+    }
+    pub fn process_operation(&self, operation: &str, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match operation {
+            "+" | ">" | "<" | "*" | "/" | "var" | "const" | "set" | "??" if exp.len() < 2 => runtime_error(format!("`{}` expects two arguments, got {}", operation, exp.len())),
+            "+" => self.process_add(&self.eval(exp[0..1].to_vec(), Rc::clone(&env_manager))?, &self.eval(exp[1..].to_vec(), Rc::clone(&env_manager))?, Rc::clone(&env_manager)),
+            ">" => self.process_bigger(&self.eval(exp[0..1].to_vec(), Rc::clone(&env_manager))?, &self.eval(exp[1..].to_vec(), Rc::clone(&env_manager))?, Rc::clone(&env_manager)),
+            "<" => self.process_smaller(&self.eval(exp[0..1].to_vec(), Rc::clone(&env_manager))?, &self.eval(exp[1..].to_vec(), Rc::clone(&env_manager))?, Rc::clone(&env_manager)),
+            "*" => self.process_mul(&self.eval(exp[0..1].to_vec(), Rc::clone(&env_manager))?, &self.eval(exp[1..].to_vec(), Rc::clone(&env_manager))?, Rc::clone(&env_manager)),
+            "/" => self.process_div(&self.eval(exp[0..1].to_vec(), Rc::clone(&env_manager))?, &self.eval(exp[1..].to_vec(), Rc::clone(&env_manager))?, Rc::clone(&env_manager)),
+            "var" => self.process_variable_declaration(&exp[0], &self.eval(exp[1..].to_vec(), Rc::clone(&env_manager))?, Rc::clone(&env_manager)),
+            "const" => self.process_constant_declaration(&exp[0], &self.eval(exp[1..].to_vec(), Rc::clone(&env_manager))?, Rc::clone(&env_manager)),
+            "set" => self.process_set_variable(&exp[0], &self.eval(exp[1..].to_vec(), Rc::clone(&env_manager))?, Rc::clone(&env_manager)),
+            "??" => self.process_default(exp, Rc::clone(&env_manager)),
+            "if" => self.process_if_else(exp, Rc::clone(&env_manager)),
+            "when" => self.process_when(exp, true, Rc::clone(&env_manager)),
+            "unless" => self.process_when(exp, false, Rc::clone(&env_manager)),
+            "cond" => self.process_cond(exp, Rc::clone(&env_manager)),
+            "while" => self.process_while(exp, Rc::clone(&env_manager)),
+            "begin" => self.process_begin(exp, Rc::clone(&env_manager)),
+            "let" | "let*" | "letrec" => self.process_let(operation, exp, Rc::clone(&env_manager)),
+            "match" => self.process_match(exp, Rc::clone(&env_manager)),
+            "def" => self.process_def(exp, Rc::clone(&env_manager)),
+            "lambda" => self.process_lambda(exp, Rc::clone(&env_manager)),
+            // A name the program bound itself means its binding, not the built-in of the same name.
+            v if env_manager.borrow().get(v.to_owned()).is_some() => self.process_value_string(v, exp, Rc::clone(&env_manager)),
+            "gc" => Ok(EvalType::Value(self.collect_garbage().into())),
+            v if string_library::is_string_builtin(v) => Ok(EvalType::Value(string_library::call(v, &self.eval_arguments(exp, Rc::clone(&env_manager))?))),
+            v if type_library::is_type_builtin(v) => Ok(EvalType::Value(type_library::call(v, &self.eval_arguments(exp, Rc::clone(&env_manager))?))),
+            v if json_library::is_json_builtin(v) => Ok(EvalType::Value(json_library::call(v, &self.eval_arguments(exp, Rc::clone(&env_manager))?))),
+            v if !self.capabilities.allows(v) => self.process_value_string(v, exp, Rc::clone(&env_manager)),
+            v if fs_library::is_fs_builtin(v) => {
+                let args = self.eval_arguments(exp, Rc::clone(&env_manager))?;
+                Ok(EvalType::Value(self.capabilities.filesystem().map_or(EvalDataType::Null, |filesystem| fs_library::call(v, &args, filesystem))))
+            }
+            v if system_library::is_system_builtin(v) => Ok(EvalType::Value(system_library::call(v, &self.eval_arguments(exp, Rc::clone(&env_manager))?, &self.random))),
+            v if io_library::is_io_builtin(v) => {
+                let args = self.eval_arguments(exp, Rc::clone(&env_manager))?;
+                Ok(EvalType::Value(io_library::call(v, &args, &mut self.io.borrow_mut())))
+            }
+            v => self.process_value_string(v, exp, Rc::clone(&env_manager)),
+        }
+    }
+    pub fn process_bigger(&self, first: &EvalType, second: &EvalType, _env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match (first, second) {
+            (EvalType::Value(EvalDataType::Number(a)), EvalType::Value(EvalDataType::Number(b))) => Ok(EvalType::Value(EvalDataType::Bool(a > b))),
+            _ => runtime_error(format!("process_add does not supported types: {:?}, {:?}", first, second))
+        }
+    }
+    pub fn process_smaller(&self, first: &EvalType, second: &EvalType, _env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match (first, second) {
+            (EvalType::Value(EvalDataType::Number(a)), EvalType::Value(EvalDataType::Number(b))) => Ok(EvalType::Value(EvalDataType::Bool(a < b))),
+            _ => runtime_error(format!("process_add does not supported types: {:?}, {:?}", first, second))
+        }
+    }
+    pub fn process_value_string(&self, value: &str, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        // Compiling the pattern dominated every variable lookup, so it is only built once.
+        static QUOTED: OnceLock<Regex> = OnceLock::new();
+        match QUOTED.get_or_init(|| Regex::new(r"(?s)^'(?P<value>.*)'$").unwrap()).captures(value) {
+            Some(captures) => Ok(EvalType::Value(EvalDataType::String(captures.name("value").unwrap().as_str().to_owned()))),
+            None => match self.process_get_variable(value, Rc::clone(&env_manager))? {
+                // `(f)` and `f` are the same tree, so a function without parameters is called even when only named.
+                EvalType::Value(EvalDataType::Function(function)) if !exp.is_empty() || function.signature.params.is_empty() => {
+                    let args = self.eval_arguments(exp, Rc::clone(&env_manager))?;
+                    Ok(EvalType::Value(self.call_function(&function, args)?))
+                }
+                variable => Ok(variable),
+            }
+        }
+    }
+    pub fn process_add(&self, first: &EvalType, second: &EvalType, _env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match (first, second) {
+            (EvalType::Value(EvalDataType::Number(a)), EvalType::Value(EvalDataType::Number(b))) => Eva::arithmetic("+", *a, *b),
+            (EvalType::Value(EvalDataType::String(a)), EvalType::Value(EvalDataType::String(b))) => Ok(EvalType::Value(EvalDataType::String(format!("{}{}", a, b)))),
+            _ => runtime_error(format!("process_add does not supported types: {:?}, {:?}", first, second))
+        }
+    }
+    // Numbers are unsigned 128-bit integers, and overflowing them is an error rather than a wrap.
+    fn arithmetic(operation: &str, a: u128, b: u128) -> EvalResult {
+        let result = match operation {
+            "+" => a.checked_add(b),
+            "*" => a.checked_mul(b),
+            _ => a.checked_div(b),
+        };
+        match result {
+            Some(result) => Ok(EvalType::Value(EvalDataType::Number(result))),
+            None if operation == "/" => runtime_error(format!("division by zero in `(/ {} {})`", a, b)),
+            None => runtime_error(format!("`({} {} {})` overflows", operation, a, b)),
+        }
+    }
+    // `(?? value fallback)` only evaluates the fallback when the value is null.
+    pub fn process_default(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match self.eval(exp[0..1].to_vec(), Rc::clone(&env_manager))? {
+            EvalType::Value(EvalDataType::Null) => self.eval(exp[1..].to_vec(), Rc::clone(&env_manager)),
+            value => Ok(value),
+        }
+    }
+    fn test_condition(&self, condition: &EvalType, env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult<bool> {
+        match self.eval(vec![condition.clone()], env_manager)? {
+            EvalType::Value(value) => self.truthiness.test(&value).map_err(InterpreterError::Runtime),
+            EvalType::Content(content, _) => runtime_error(format!("test_condition does not supported types: {:?}", content)),
+        }
+    }
+    fn eval_statements(&self, statements: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let mut result = EvalType::Value(EvalDataType::Null);
+        for statement in statements {
+            result = self.eval(vec![statement.clone()], Rc::clone(&env_manager))?;
+        }
+        Ok(result)
+    }
+    // `(if cond then [else])`, or the older `(if (cond then else))` with its parts wrapped in one list.
+    pub fn if_parts(exp: &[EvalType]) -> &[EvalType] {
+        match exp {
+            [EvalType::Content(wrapped, _)] if wrapped.len() >= 2 => wrapped,
+            parts => parts,
+        }
+    }
+    pub fn process_if_else(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let parts = Eva::if_parts(exp);
+        if parts.len() < 2 || parts.len() > 3 {
+            return runtime_error(format!("`if` expects a condition, a branch and an optional else branch, got {} parts", parts.len()));
+        }
+        if self.test_condition(&parts[0], Rc::clone(&env_manager))? {
+            return self.eval(vec![parts[1].clone()], env_manager);
+        }
+        self.eval_statements(&parts[2..], env_manager)
+    }
+    pub fn process_when(&self, exp: &[EvalType], expected: bool, env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let condition = match exp.first() {
+            Some(condition) => condition,
+            None => return runtime_error(format!("`{}` expects a condition", if expected { "when" } else { "unless" })),
+        };
+        match self.test_condition(condition, Rc::clone(&env_manager))? == expected {
+            true => self.eval_statements(&exp[1..], env_manager),
+            false => Ok(EvalType::Value(EvalDataType::Null)),
+        }
+    }
+    // `(cond (test body…)… (else body…))` runs the body of the first clause whose test holds.
+    pub fn process_cond(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        for clause in exp {
+            match clause {
+                EvalType::Content(clause, _) if clause.first() == Some(&EvalType::Value(EvalDataType::String("else".to_owned()))) => {
+                    return self.eval_statements(&clause[1..], env_manager);
+                }
+                EvalType::Content(clause, _) if !clause.is_empty() => {
+                    if self.test_condition(&clause[0], Rc::clone(&env_manager))? {
+                        return self.eval_statements(&clause[1..], env_manager);
+                    }
+                }
+                other => return runtime_error(format!("process_cond does not supported types: {:?}", other)),
+            }
+        }
+        Ok(EvalType::Value(EvalDataType::Null))
+    }
+    pub fn process_while(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let (condition, body) = match exp.first() {
+            Some(EvalType::Content(exp_content, _)) if exp_content.len() >= 2 => (&exp_content[0], &exp_content[1]),
+            _ => return runtime_error(format!("process_while does not supported types: {:?}", exp)),
+        };
+        let mut result = EvalType::Value(EvalDataType::Null);
+        while self.test_condition(condition, Rc::clone(&env_manager))? {
+            result = self.eval(vec![body.clone()], Rc::clone(&env_manager))?;
+        }
+        Ok(result)
+    }
+    pub fn process_mul(&self, first: &EvalType, second: &EvalType, _env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match (first, second) {
+            (EvalType::Value(EvalDataType::Number(a)), EvalType::Value(EvalDataType::Number(b))) => Eva::arithmetic("*", *a, *b),
+            _ => runtime_error(format!("process_mul does not supported types: {:?}, {:?}", first, second))
+        }
+    }
+    pub fn process_div(&self, first: &EvalType, second: &EvalType, _env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match (first, second) {
+            (EvalType::Value(EvalDataType::Number(a)), EvalType::Value(EvalDataType::Number(b))) => Eva::arithmetic("/", *a, *b),
+            _ => runtime_error(format!("process_mul does not supported types: {:?}, {:?}", first, second))
+        }
+    }
+    pub fn process_variable_declaration(&self, first: &EvalType, second: &EvalType, env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let variable_value = match second {
+            EvalType::Value(variable_value) => variable_value,
+            _ => return runtime_error(format!("process_variable_declaration does not supported types: {:?}, {:?}", first, second)),
+        };
+        match VarTarget::parse(first).map_err(InterpreterError::Runtime)? {
+            VarTarget::Name(variable_name, declared) => {
+                if let Some(declared) = declared.filter(|declared| !declared.accepts(variable_value)) {
+                    return runtime_error(format!("variable `{}` is declared {} but was given {}", variable_name, declared, variable_value));
+                }
+                Ok(EvalType::Value(self.define_variable(&variable_name, variable_value.clone(), env_manager)?))
+            }
+            // `(var (a b . rest) value)` binds every name of the pattern in the current environment.
+            VarTarget::Pattern(pattern) => {
+                let bindings = pattern.bind(variable_value)
+                    .ok_or_else(|| InterpreterError::Runtime(format!("value {} does not match pattern {}", variable_value, print_inline(first))))?;
+                for (name, value) in bindings {
+                    self.define_variable(&name, value, Rc::clone(&env_manager))?;
+                }
+                Ok(EvalType::Value(variable_value.clone()))
+            }
+        }
+    }
+    pub fn process_constant_declaration(&self, first: &EvalType, second: &EvalType, env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        match (first, second) {
+            (EvalType::Value(EvalDataType::String(constant_name)), EvalType::Value(constant_value)) => {
+                let value = self.define_variable(constant_name, constant_value.clone(), Rc::clone(&env_manager))?;
+                env_manager.borrow_mut().mark_constant(constant_name);
+                Ok(EvalType::Value(value))
+            }
+            _ => runtime_error(format!("process_constant_declaration does not supported types: {:?}, {:?}", first, second))
+        }
+    }
+    fn define_variable(&self, variable_name: &str, variable_value: EvalDataType, env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult<EvalDataType> {
+        let mut env = env_manager.borrow_mut();
+        if env.is_shadowing(variable_name) {
+            self.warn(format!("variable `{}` shadows a binding from an enclosing scope", variable_name));
+        }
+        match env.define(variable_name.to_owned(), variable_value.clone()) {
+            Ok(value) => Ok(value),
+            // Constants are never redeclared, so the optimizer can inline them.
+            Err(error) => match self.strictness {
+                Strictness::Lenient if !env.is_constant(variable_name) => {
+                    self.warn(error.to_string());
+                    Ok(env.redefine(variable_name.to_owned(), variable_value))
+                }
+                _ => runtime_error(error.to_string()),
+            }
+        }
+    }
+    pub fn process_set_variable(&self, first: &EvalType, second: &EvalType, env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let assigned = match (first, second) {
+            (EvalType::Value(EvalDataType::String(variable_name)), EvalType::Value(variable_value)) => env_manager.borrow_mut().assign(variable_name.to_owned(), variable_value.clone()),
+            _ => return runtime_error(format!("process_set_variable does not supported types: {:?}, {:?}", first, second)),
+        };
+        match assigned {
+            Ok(value) => Ok(EvalType::Value(value)),
+            Err(error) => runtime_error(error.to_string()),
+        }
+    }
+    pub fn process_get_variable(&self, var_name: &str, env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        if let Some(value) = env_manager.borrow().get(var_name.to_string()) {
+            return Ok(EvalType::Value(value));
+        }
+        runtime_error(format!("Variable {} does not exist", var_name))
+    }
+
+    fn process_begin(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let mut result = EvalType::Value(EvalDataType::Null);
+        let block_env = self.new_environment(&env_manager);
+
+        for e in Eva::block_statements(exp) {
+            result = self.eval(vec![e], Rc::clone(&block_env))?;
+        }
+        Ok(result)
+    }
+    // `(let ((name value)…) body…)`; `None` when the bindings are not a list of `(name value)` pairs.
+    pub fn let_parts(exp: &[EvalType]) -> Option<(Vec<LetBinding<'_>>, &[EvalType])> {
+        let bindings = match exp.first() {
+            Some(EvalType::Content(bindings, _)) => bindings,
+            _ => return None,
+        };
+        let bindings = bindings.iter().map(|binding| match binding {
+            EvalType::Content(binding, _) => match binding.as_slice() {
+                [EvalType::Value(EvalDataType::String(name)), value @ ..] if !value.is_empty() => Some((name.as_str(), value)),
+                _ => None,
+            },
+            _ => None,
+        }).collect::<Option<Vec<LetBinding>>>()?;
+        Some((bindings, &exp[1..]))
+    }
+    // `let` evaluates every value in the enclosing environment, `let*` lets each value see the bindings before it
+    // and `letrec` binds every name before evaluating any value, so local functions can call each other.
+    fn process_let(&self, kind: &str, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let (bindings, body) = Eva::let_parts(exp).ok_or_else(|| InterpreterError::Runtime(format!("`{}` expects a list of (name value) bindings", kind)))?;
+        let child = |parent: &Rc<RefCell<EnvironmentManagerRaw>>| self.new_environment(parent);
+        let let_env = match kind {
+            "let" => {
+                let values = bindings.iter().map(|(_, value)| self.eval_value(value, Rc::clone(&env_manager))).collect::<EvalResult<Vec<EvalDataType>>>()?;
+                let let_env = child(&env_manager);
+                for ((name, _), value) in bindings.iter().zip(values) {
+                    self.define_variable(name, value, Rc::clone(&let_env))?;
+                }
+                let_env
+            }
+            "let*" => bindings.iter().try_fold(env_manager, |env, (name, value)| {
+                let value = self.eval_value(value, Rc::clone(&env))?;
+                let let_env = child(&env);
+                self.define_variable(name, value, Rc::clone(&let_env))?;
+                Ok(let_env)
+            })?,
+            _ => {
+                let let_env = child(&env_manager);
+                for (name, _) in &bindings {
+                    self.define_variable(name, EvalDataType::Null, Rc::clone(&let_env))?;
+                }
+                for (name, value) in &bindings {
+                    let value = self.eval_value(value, Rc::clone(&let_env))?;
+                    let_env.borrow_mut().redefine(name.to_string(), value);
+                }
+                let_env
+            }
+        };
+        self.eval_statements(body, let_env)
+    }
+    // `(match value (pattern [when guard] body…)…)` runs the first clause whose pattern and guard accept the value,
+    // with the pattern's bindings in a new environment.
+    fn process_match(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let value = match exp.first() {
+            Some(value) => self.eval_value(std::slice::from_ref(value), Rc::clone(&env_manager))?,
+            None => return runtime_error("`match` expects a value and clauses".to_owned()),
+        };
+        for clause in &exp[1..] {
+            let (pattern, guard, body) = clause_parts(clause)
+                .ok_or_else(|| InterpreterError::Runtime(format!("`match` clause must be (pattern [when guard] body…), not {}", print_inline(clause))))?;
+            let pattern = Pattern::parse(pattern).map_err(InterpreterError::Runtime)?;
+            let bindings = match pattern.bind(&value) {
+                Some(bindings) => bindings,
+                None => continue,
+            };
+            let clause_env = self.new_environment(&env_manager);
+            for (name, value) in bindings {
+                clause_env.borrow_mut().redefine(name, value);
+            }
+            let accepted = match guard {
+                Some(guard) => self.test_condition(guard, Rc::clone(&clause_env))?,
+                None => true,
+            };
+            if accepted {
+                return self.eval_statements(body, clause_env);
+            }
+        }
+        Ok(EvalType::Value(EvalDataType::Null))
+    }
+    fn process_def(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let name = match exp.first() {
+            Some(EvalType::Value(EvalDataType::String(name))) => name.clone(),
+            other => return runtime_error(format!("process_def does not supported types: {:?}", other)),
+        };
+        let signature = Signature::parse(&exp[1..]).map_err(|error| InterpreterError::Runtime(format!("`{}`: {}", name, error)))?;
+        let function = Function { name: name.clone(), signature, env: Rc::clone(&env_manager) };
+        Ok(EvalType::Value(self.define_variable(&name, EvalDataType::Function(Rc::new(function)), env_manager)?))
+    }
+    fn process_lambda(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult {
+        let signature = Signature::parse(exp).map_err(|error| InterpreterError::Runtime(format!("`lambda`: {}", error)))?;
+        Ok(EvalType::Value(EvalDataType::Function(Rc::new(Function { name: "lambda".to_owned(), signature, env: env_manager }))))
+    }
+    pub fn call_function(&self, function: &Function, args: Vec<EvalDataType>) -> EvalResult<EvalDataType> {
+        let signature = &function.signature;
+        if args.len() != signature.params.len() {
+            return runtime_error(format!("`{}` expects {} arguments, got {}", function.name, signature.params.len(), args.len()));
+        }
+        let call_env = self.new_environment(&function.env);
+        for (param, arg) in signature.params.iter().zip(args) {
+            if let Some(annotation) = &param.annotation {
+                if !annotation.accepts(&arg) {
+                    return runtime_error(format!("argument `{}` of `{}` expects {}, got {}", param.name, function.name, annotation, arg));
+                }
+            }
+            call_env.borrow_mut().redefine(param.name.clone(), arg);
+        }
+        if let Some(debugger) = &self.debugger {
+            debugger.borrow_mut().enter_function(&function.name, &call_env);
+        }
+        let result = self.eval_statements(&signature.body, Rc::clone(&call_env));
+        if let Some(debugger) = &self.debugger {
+            debugger.borrow_mut().leave_function();
+        }
+        let result = match result? {
+            EvalType::Value(value) => value,
+            EvalType::Content(content, _) => return runtime_error(format!("call_function does not supported types: {:?}", content)),
+        };
+        if let Some(return_type) = &signature.return_type {
+            if !return_type.accepts(&result) {
+                return runtime_error(format!("`{}` is declared to return {} but returned {}", function.name, return_type, result));
+            }
+        }
+        Ok(result)
+    }
+    // A block is either `(begin ((stmt) (stmt)))` or `(begin (stmt) (stmt))`.
+    fn block_statements(exp: &[EvalType]) -> Vec<EvalType> {
+        match exp.first() {
+            Some(EvalType::Content(statements, _)) if matches!(statements.first(), Some(EvalType::Content(..))) => statements.clone(),
+            _ => exp.to_vec(),
+        }
+    }
+    fn eval_arguments(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult<Vec<EvalDataType>> {
+        exp.iter().map(|e| self.eval_value(std::slice::from_ref(e), Rc::clone(&env_manager))).collect()
+    }
+    pub(crate) fn eval_value(&self, exp: &[EvalType], env_manager: Rc<RefCell<EnvironmentManagerRaw>>) -> EvalResult<EvalDataType> {
+        match self.eval(exp.to_vec(), env_manager)? {
+            EvalType::Value(value) => Ok(value),
+            EvalType::Content(content, _) => runtime_error(format!("eval_value does not supported types: {:?}", content)),
+        }
+    }
+    fn warn(&self, message: String) {
+        self.diagnostics.borrow_mut().push(Diagnostic::warning(message));
+    }
+}
+
+
+pub fn get_environment_manager() -> Rc<RefCell<EnvironmentManagerRaw>> {
+    Rc::new(RefCell::new(EnvironmentManagerRaw::new(Some(HashMap::from([
+        ("VERSION".to_owned(), EvalDataType::String("1.0.0".to_owned())),
+    ])), None)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::{Eva, EvalDataType, EvalType, InterpreterError, Span, get_environment_manager};
+    use crate::diagnostics::{Diagnostic, Strictness};
+    use crate::parser::parse;
+    use crate::truthiness::Truthiness;
+
+    #[test]
+    fn test_identity() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![EvalType::Value(EvalDataType::Number(1))], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(1)));
+        assert_eq!(eva.eval(vec![EvalType::Value(EvalDataType::String("'data to check'".to_owned()))], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::String("data to check".to_owned())));
+    }
+
+    #[test]
+    fn test_trees_compare_without_spans() {
+        assert_ne!(Span::new(1, 1), Span::new(2, 1));
+        let written_at = |line| EvalType::Content(vec![EvalType::Value(EvalDataType::Number(1))], Span::new(line, 1));
+        assert_eq!(written_at(1), written_at(2));
+    }
+
+    #[test]
+    fn test_add() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("+".to_owned())),
+                EvalType::Value(EvalDataType::Number(1)),
+                EvalType::Value(EvalDataType::Number(3)),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(4)));
+    }
+
+    #[test]
+    fn test_mul() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("*".to_owned())),
+                EvalType::Value(EvalDataType::Number(2)),
+                EvalType::Value(EvalDataType::Number(3)),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(6)));
+    }
+
+    #[test]
+    fn test_div() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("/".to_owned())),
+                EvalType::Value(EvalDataType::Number(10)),
+                EvalType::Value(EvalDataType::Number(2)),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(5)));
+    }
+
+    #[test]
+    fn test_declare_variable() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("var".to_owned())),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+                EvalType::Value(EvalDataType::Number(8)),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(8)));
+
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("var".to_owned())),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+                EvalType::Value(EvalDataType::String("'value'".to_owned())),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::String("value".to_owned())));
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("var".to_owned())),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+                EvalType::Value(EvalDataType::Number(88)),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(88)));
+    }
+
+    #[test]
+    fn test_block() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("x".to_owned())),
+                    EvalType::Value(EvalDataType::Number(10)),
+                ], Span::NONE),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("y".to_owned())),
+                    EvalType::Value(EvalDataType::Number(10)),
+                ], Span::NONE),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("+".to_owned())),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("*".to_owned())),
+                        EvalType::Value(EvalDataType::String("x".to_owned())),
+                        EvalType::Value(EvalDataType::String("y".to_owned())),
+                    ], Span::NONE),
+                    EvalType::Value(EvalDataType::Number(10)),
+                ], Span::NONE),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(110)));
+    }
+
+    #[test]
+    fn test_nested_block() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("x".to_owned())),
+                    EvalType::Value(EvalDataType::Number(10)),
+                    EvalType::Value(EvalDataType::String("begin".to_owned())),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("var".to_owned())),
+                        EvalType::Value(EvalDataType::String("x".to_owned())),
+                        EvalType::Value(EvalDataType::Number(20)),
+                        EvalType::Value(EvalDataType::String("x".to_owned())),
+                    ], Span::NONE),
+                ], Span::NONE),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(10)));
+    }
+
+    #[test]
+    fn test_nested_block_variable() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("var".to_owned())),
+                        EvalType::Value(EvalDataType::String("value".to_owned())),
+                        EvalType::Value(EvalDataType::Number(10)),
+                    ], Span::NONE),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("var".to_owned())),
+                        EvalType::Value(EvalDataType::String("result".to_owned())),
+                        EvalType::Value(EvalDataType::String("begin".to_owned())),
+                        EvalType::Content(vec![
+                            EvalType::Content(vec![
+                                EvalType::Value(EvalDataType::String("var".to_owned())),
+                                EvalType::Value(EvalDataType::String("x".to_owned())),
+                                EvalType::Content(vec![
+                                    EvalType::Value(EvalDataType::String("+".to_owned())),
+                                    EvalType::Value(EvalDataType::String("value".to_owned())),
+                                    EvalType::Value(EvalDataType::Number(10)),
+                                ], Span::NONE),
+                                EvalType::Value(EvalDataType::String("x".to_owned())),
+                            ], Span::NONE),
+                        ], Span::NONE),
+                        EvalType::Value(EvalDataType::String("result".to_owned())),
+                    ], Span::NONE),
+                ], Span::NONE),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(20)));
+    }
+
+    #[test]
+    fn test_set_variable() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("var".to_owned())),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+                EvalType::Value(EvalDataType::Number(8)),
+            ], Span::NONE)
+        ], Rc::clone(&env_manager)).unwrap();
+
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("set".to_owned())),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+                EvalType::Value(EvalDataType::Number(11)),
+            ], Span::NONE)
+        ], Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(11)));
+    }
+
+    #[test]
+    fn test_variable_of_every_type() {
+        let eva = Eva::new();
+        let cases = vec![
+            (EvalType::Value(EvalDataType::Number(8)), EvalDataType::Number(8)),
+            (EvalType::Value(EvalDataType::String("'text'".to_owned())), EvalDataType::String("text".to_owned())),
+            (EvalType::Value(EvalDataType::Bool(true)), EvalDataType::Bool(true)),
+            (EvalType::Value(EvalDataType::Bool(false)), EvalDataType::Bool(false)),
+        ];
+        for (declared, expected) in cases.clone() {
+            let env_manager = get_environment_manager();
+            assert_eq!(eva.eval(vec![
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("x".to_owned())),
+                    declared,
+                ], Span::NONE)
+            ], Rc::clone(&env_manager)).unwrap(), EvalType::Value(expected.clone()));
+            assert_eq!(eva.eval(vec![EvalType::Value(EvalDataType::String("x".to_owned()))], Rc::clone(&env_manager)).unwrap(), EvalType::Value(expected.clone()));
+
+            for (assigned, expected) in cases.clone() {
+                assert_eq!(eva.eval(vec![
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("set".to_owned())),
+                        EvalType::Value(EvalDataType::String("x".to_owned())),
+                        assigned,
+                    ], Span::NONE)
+                ], Rc::clone(&env_manager)).unwrap(), EvalType::Value(expected.clone()));
+                assert_eq!(eva.eval(vec![EvalType::Value(EvalDataType::String("x".to_owned()))], Rc::clone(&env_manager)).unwrap(), EvalType::Value(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_variable_from_expression() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("big".to_owned())),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String(">".to_owned())),
+                        EvalType::Value(EvalDataType::Number(3)),
+                        EvalType::Value(EvalDataType::Number(2)),
+                    ], Span::NONE),
+                ], Span::NONE),
+                EvalType::Value(EvalDataType::String("big".to_owned())),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Bool(true)));
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("name".to_owned())),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("upper".to_owned())),
+                        EvalType::Content(vec![
+                            EvalType::Value(EvalDataType::String("substr".to_owned())),
+                            EvalType::Value(EvalDataType::String("'  evaluator '".to_owned())),
+                            EvalType::Value(EvalDataType::Number(2)),
+                            EvalType::Value(EvalDataType::Number(5)),
+                        ], Span::NONE),
+                    ], Span::NONE),
+                ], Span::NONE),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("format".to_owned())),
+                    EvalType::Value(EvalDataType::String("'Hello {}, {} chars!'".to_owned())),
+                    EvalType::Value(EvalDataType::String("name".to_owned())),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("str-len".to_owned())),
+                        EvalType::Value(EvalDataType::String("name".to_owned())),
+                    ], Span::NONE),
+                ], Span::NONE),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::String("Hello EVA, 3 chars!".to_owned())));
+    }
+
+    #[test]
+    fn test_type_conversion() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("+".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("to-number".to_owned())),
+                    EvalType::Value(EvalDataType::String("'40'".to_owned())),
+                ], Span::NONE),
+                EvalType::Value(EvalDataType::Number(2)),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(42)));
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("type-of".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("to-string".to_owned())),
+                    EvalType::Value(EvalDataType::Number(42)),
+                ], Span::NONE),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::String("string".to_owned())));
+    }
+
+    #[test]
+    fn test_redeclare_variable_strict() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("x".to_owned())),
+                    EvalType::Value(EvalDataType::Number(1)),
+                ], Span::NONE),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("x".to_owned())),
+                    EvalType::Value(EvalDataType::Number(2)),
+                ], Span::NONE),
+            ], Span::NONE)
+        ], get_environment_manager()), Err(InterpreterError::Runtime("variable `x` is already declared in this scope".to_owned())));
+    }
+
+    #[test]
+    fn test_redeclare_variable_lenient() {
+        let eva = Eva::with_strictness(Strictness::Lenient);
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("x".to_owned())),
+                    EvalType::Value(EvalDataType::Number(1)),
+                ], Span::NONE),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("x".to_owned())),
+                    EvalType::Value(EvalDataType::Number(2)),
+                ], Span::NONE),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(2)));
+        assert_eq!(eva.diagnostics(), vec![
+            Diagnostic::warning("variable `x` is already declared in this scope".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_set_undeclared_variable() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("var".to_owned())),
+                EvalType::Value(EvalDataType::String("counter".to_owned())),
+                EvalType::Value(EvalDataType::Number(0)),
+            ], Span::NONE)
+        ], Rc::clone(&env_manager)).unwrap();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("set".to_owned())),
+                    EvalType::Value(EvalDataType::String("countr".to_owned())),
+                    EvalType::Value(EvalDataType::Number(1)),
+                ], Span::NONE),
+            ], Span::NONE)
+        ], Rc::clone(&env_manager)), Err(InterpreterError::Runtime("assignment to undeclared variable `countr`; did you mean `counter`?".to_owned())));
+    }
+
+    #[test]
+    fn test_shadowing_warning() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("var".to_owned())),
+                    EvalType::Value(EvalDataType::String("x".to_owned())),
+                    EvalType::Value(EvalDataType::Number(10)),
+                ], Span::NONE),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("begin".to_owned())),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("var".to_owned())),
+                        EvalType::Value(EvalDataType::String("x".to_owned())),
+                        EvalType::Value(EvalDataType::Number(20)),
+                    ], Span::NONE),
+                ], Span::NONE),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(10)));
+        assert_eq!(eva.diagnostics(), vec![
+            Diagnostic::warning("variable `x` shadows a binding from an enclosing scope".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_if_else() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("var".to_owned())),
+                        EvalType::Value(EvalDataType::String("x".to_owned())),
+                        EvalType::Value(EvalDataType::Number(10)),
+                    ], Span::NONE),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("var".to_owned())),
+                        EvalType::Value(EvalDataType::String("y".to_owned())),
+                        EvalType::Value(EvalDataType::Number(0)),
+                    ], Span::NONE),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("if".to_owned())),
+                        EvalType::Content(vec![
+                            EvalType::Content(vec![
+                                EvalType::Value(EvalDataType::String(">".to_owned())),
+                                EvalType::Value(EvalDataType::String("x".to_owned())),
+                                EvalType::Value(EvalDataType::Number(10)),
+                            ], Span::NONE),
+                            EvalType::Content(vec![
+                                EvalType::Value(EvalDataType::String("set".to_owned())),
+                                EvalType::Value(EvalDataType::String("y".to_owned())),
+                                EvalType::Value(EvalDataType::Number(20)),
+                            ], Span::NONE),
+                            EvalType::Content(vec![
+                                EvalType::Value(EvalDataType::String("set".to_owned())),
+                                EvalType::Value(EvalDataType::String("y".to_owned())),
+                                EvalType::Value(EvalDataType::Number(30)),
+                            ], Span::NONE),
+                        ], Span::NONE),
+                    ], Span::NONE),
+                ], Span::NONE),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(30)));
+    }
+
+    #[test]
+    fn test_while() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("var".to_owned())),
+                        EvalType::Value(EvalDataType::String("counter".to_owned())),
+                        EvalType::Value(EvalDataType::Number(0)),
+                    ], Span::NONE),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("var".to_owned())),
+                        EvalType::Value(EvalDataType::String("result".to_owned())),
+                        EvalType::Value(EvalDataType::Number(0)),
+                    ], Span::NONE),
+                    EvalType::Content(vec![
+                        EvalType::Value(EvalDataType::String("while".to_owned())),
+                        EvalType::Content(vec![
+                            EvalType::Content(vec![
+                                EvalType::Value(EvalDataType::String("<".to_owned())),
+                                EvalType::Value(EvalDataType::String("counter".to_owned())),
+                                EvalType::Value(EvalDataType::Number(10)),
+                            ], Span::NONE),
+                            EvalType::Content(vec![
+                                EvalType::Value(EvalDataType::String("begin".to_owned())),
+                                EvalType::Content(vec![
+                                    EvalType::Content(vec![
+                                        EvalType::Value(EvalDataType::String("set".to_owned())),
+                                        EvalType::Value(EvalDataType::String("result".to_owned())),
+                                        EvalType::Content(vec![
+                                            EvalType::Value(EvalDataType::String("+".to_owned())),
+                                            EvalType::Value(EvalDataType::String("result".to_owned())),
+                                            EvalType::Value(EvalDataType::Number(1)),
+                                        ], Span::NONE),
+                                    ], Span::NONE),
+                                    EvalType::Content(vec![
+                                        EvalType::Value(EvalDataType::String("set".to_owned())),
+                                        EvalType::Value(EvalDataType::String("counter".to_owned())),
+                                        EvalType::Content(vec![
+                                            EvalType::Value(EvalDataType::String("+".to_owned())),
+                                            EvalType::Value(EvalDataType::String("counter".to_owned())),
+                                            EvalType::Value(EvalDataType::Number(1)),
+                                        ], Span::NONE),
+                                    ], Span::NONE),
+                                ], Span::NONE),
+                            ], Span::NONE),
+                        ], Span::NONE),
+                        EvalType::Value(EvalDataType::String("result".to_owned())),
+                    ], Span::NONE),
+                ], Span::NONE),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+            ], Span::NONE)
+        ], get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(10)));
+    }
+
+    #[test]
+    fn test_def_and_call() {
+        let eva = Eva::new();
+        let program = parse(concat!(
+            "(begin (def square ((x : number)) -> number (* x x))",
+            " (def count-up (i limit) (if ((< i limit) (count-up (+ i 1) limit) i)))",
+            " (+ (square 4) (count-up 0 5)))",
+        )).unwrap();
+        assert_eq!(eva.eval(program, get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(21)));
+    }
+
+    #[test]
+    fn test_lambda() {
+        let eva = Eva::new();
+        let program = parse("(begin (var double (lambda (x) (+ x x))) (double 21))").unwrap();
+        assert_eq!(eva.eval(program, get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::Number(42)));
+    }
+
+    #[test]
+    fn test_annotated_variable() {
+        let eva = Eva::new();
+        let program = parse("(begin (var (name : (or string error)) \"eva\") name)").unwrap();
+        assert_eq!(eva.eval(program, get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::String("eva".to_owned())));
+    }
+
+    #[test]
+    fn test_annotated_variable_mismatch() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(parse("(var (x : number) \"ten\")").unwrap(), get_environment_manager()), Err(InterpreterError::Runtime("variable `x` is declared number but was given \"ten\"".to_owned())));
+    }
+
+    #[test]
+    fn test_annotated_argument_mismatch() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(parse("(begin (def square ((x : number)) (* x x)) (square true))").unwrap(), get_environment_manager()), Err(InterpreterError::Runtime("argument `x` of `square` expects number, got true".to_owned())));
+        // Parameters are annotated with `:` like variables, so the older `(x number)` is rejected.
+        assert_eq!(eva.eval(parse("(def half ((x number)) (/ x 2))").unwrap(), get_environment_manager()),
+            Err(InterpreterError::Runtime("`half`: a parameter is a name or (name : type), not (x number)".to_owned())));
+    }
+
+    #[test]
+    fn test_set_constant() {
+        let eva = Eva::new();
+        assert_eq!(eva.eval(parse("(begin (const limit 10) (set limit 20))").unwrap(), get_environment_manager()), Err(InterpreterError::Runtime("cannot assign to constant `limit`".to_owned())));
+    }
+
+    #[test]
+    fn test_redeclare_constant_lenient() {
+        let eva = Eva::with_strictness(Strictness::Lenient);
+        assert_eq!(eva.eval(parse("(begin (const limit 10) (var limit 20))").unwrap(), get_environment_manager()), Err(InterpreterError::Runtime("variable `limit` is already declared in this scope".to_owned())));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let eva = Eva::new();
+        let program = parse(concat!(
+            "(begin (var config (json-parse \"{\\\"name\\\": \\\"eva\\\", \\\"ports\\\": [80, 443]}\"))",
+            " (json-stringify (get config \"ports\")))",
+        )).unwrap();
+        assert_eq!(eva.eval(program, get_environment_manager()).unwrap(), EvalType::Value(EvalDataType::String("[80,443]".to_owned())));
+    }
+
+    #[test]
+    fn test_null() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        assert_eq!(eva.eval(parse("(begin)").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Null));
+        assert_eq!(eva.eval(parse("(while (false 1))").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Null));
+        assert_eq!(eva.eval(parse("(null? (begin))").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Bool(true)));
+        assert_eq!(eva.eval(parse("(null? 0)").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Bool(false)));
+    }
+
+    #[test]
+    fn test_default_operator() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        assert_eq!(eva.eval(parse("(?? null \"fallback\")").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::String("fallback".to_owned())));
+        assert_eq!(eva.eval(parse("(?? 0 \"fallback\")").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(0)));
+        // The fallback is not evaluated when the value is present.
+        assert_eq!(eva.eval(parse("(?? VERSION (set missing 1))").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::String("1.0.0".to_owned())));
+    }
+
+    #[test]
+    fn test_standard_if() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        assert_eq!(eva.eval(parse("(if (> 2 1) \"yes\" \"no\")").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::String("yes".to_owned())));
+        assert_eq!(eva.eval(parse("(if (> 1 2) \"yes\")").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Null));
+        assert_eq!(eva.eval(parse("(if ((> 1 2) 1 2))").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(2)));
+    }
+
+    #[test]
+    fn test_when_unless_cond() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        eva.eval(parse("(var x 5)").unwrap(), Rc::clone(&env_manager)).unwrap();
+        assert_eq!(eva.eval(parse("(when (> x 1) (set x (+ x 1)) x)").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(6)));
+        assert_eq!(eva.eval(parse("(unless (> x 1) (set x 0))").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Null));
+        let cond = "(cond ((< x 3) \"small\") ((< x 10) \"medium\") (else \"large\"))";
+        assert_eq!(eva.eval(parse(cond).unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::String("medium".to_owned())));
+        assert_eq!(eva.eval(parse("(cond ((> x 10) 1))").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Null));
+    }
+
+    #[test]
+    fn test_lenient_truthiness() {
+        let eva = Eva::new().with_truthiness(Truthiness::Lenient);
+        let env_manager = get_environment_manager();
+        assert_eq!(eva.eval(parse("(if null 1 2)").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(2)));
+        assert_eq!(eva.eval(parse("(if 0 1 2)").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(1)));
+    }
+
+    #[test]
+    fn test_strict_truthiness() {
+        assert_eq!(Eva::new().eval(parse("(if 0 1 2)").unwrap(), get_environment_manager()), Err(InterpreterError::Runtime("condition must be a bool, got 0".to_owned())));
+    }
+
+    #[test]
+    fn test_let() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        eva.eval(parse("(var x 10)").unwrap(), Rc::clone(&env_manager)).unwrap();
+        assert_eq!(eva.eval(parse("(let ((x 1) (y x)) (+ x y))").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(11)));
+        assert_eq!(eva.eval(parse("(let* ((x 1) (y x)) (+ x y))").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(2)));
+        assert_eq!(eva.eval(parse("(let* ((x 1) (x (+ x 1))) x)").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(2)));
+        // The bindings go away with the body.
+        assert_eq!(eva.eval(parse("x").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(10)));
+        assert_eq!(env_manager.borrow().get("y".to_owned()), None);
+    }
+
+    #[test]
+    fn test_letrec() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        let source = concat!(
+            "(letrec ((up (lambda (n) (if (< n 10) (down (+ n 1)) n)))",
+            " (down (lambda (n) (up (+ n 2)))))",
+            " (up 0))",
+        );
+        assert_eq!(eva.eval(parse(source).unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Number(12)));
+    }
+
+    #[test]
+    fn test_let_malformed_bindings() {
+        assert_eq!(Eva::new().eval(parse("(let (x 1) x)").unwrap(), get_environment_manager()), Err(InterpreterError::Runtime("`let` expects a list of (name value) bindings".to_owned())));
+    }
+
+    #[test]
+    fn test_match() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        let describe = "(def describe (value) (match value (0 \"zero\") ((first . _) first) ((map \"name\" name) name) (n when (> n 9) \"big\") (_ \"other\")))";
+        eva.eval(parse(describe).unwrap(), Rc::clone(&env_manager)).unwrap();
+        let cases = [
+            ("(describe 0)", "zero"),
+            ("(describe 12)", "big"),
+            ("(describe 3)", "other"),
+            ("(describe (split \"a,b\" \",\"))", "a"),
+            ("(describe (json-parse \"{\\\"name\\\": \\\"eva\\\"}\"))", "eva"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eva.eval(parse(source).unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::String(expected.to_owned())), "{}", source);
+        }
+        assert_eq!(eva.eval(parse("(match 1 (2 \"two\"))").unwrap(), Rc::clone(&env_manager)).unwrap(), EvalType::Value(EvalDataType::Null));
+    }
+
+    #[test]
+    fn test_destructuring_var() {
+        let eva = Eva::new();
+        let env_manager = get_environment_manager();
+        eva.eval(parse("(var (a b . rest) (split \"x,y,z\" \",\"))").unwrap(), Rc::clone(&env_manager)).unwrap();
+        assert_eq!(env_manager.borrow().get("b".to_owned()), Some(EvalDataType::from("y")));
+        assert_eq!(env_manager.borrow().get("rest".to_owned()), Some(EvalDataType::from(vec!["z"])));
+    }
+
+    #[test]
+    fn test_malformed_match_clause() {
+        assert_eq!(Eva::new().eval(parse("(match 1 (0 \"zero\") 2)").unwrap(), get_environment_manager()),
+            Err(InterpreterError::Runtime("`match` clause must be (pattern [when guard] body…), not 2".to_owned())));
+    }
+
+    #[test]
+    fn test_destructuring_mismatch() {
+        assert_eq!(Eva::new().eval(parse("(var (a b) (split \"x\" \",\"))").unwrap(), get_environment_manager()), Err(InterpreterError::Runtime("value (\"x\") does not match pattern (a b)".to_owned())));
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use crate::diagnostics::Severity;
+use crate::get_environment_manager;
+use crate::parser::{lower, parse_syntax, SyntaxNode};
+use crate::pattern::{Pattern, VarTarget};
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Rule {
+    UndeclaredSet,
+    IfArity,
+    UnusedVariable,
+    UnreachableCode,
+    StringNumberAdd,
+}
+
+pub const RULES: [Rule; 5] = [Rule::UndeclaredSet, Rule::IfArity, Rule::UnusedVariable, Rule::UnreachableCode, Rule::StringNumberAdd];
+
+impl Rule {
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::UndeclaredSet => "undeclared-set",
+            Rule::IfArity => "if-arity",
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::StringNumberAdd => "string-number-add",
+        }
+    }
+    pub fn from_id(id: &str) -> Option<Rule> {
+        RULES.iter().copied().find(|rule| rule.id() == id)
+    }
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Rule::UndeclaredSet | Rule::IfArity | Rule::StringNumberAdd => Severity::Error,
+            Rule::UnusedVariable | Rule::UnreachableCode => Severity::Warning,
+        }
+    }
+}
+
+/// Per-rule severity; a rule mapped to `None` is switched off.
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    levels: HashMap<Rule, Option<Severity>>,
+}
+
+impl Default for LintConfig {
+    fn default() -> LintConfig {
+        LintConfig { levels: RULES.iter().map(|rule| (*rule, Some(rule.default_severity()))).collect() }
+    }
+}
+
+impl LintConfig {
+    pub fn set(&mut self, rule: Rule, level: Option<Severity>) {
+        self.levels.insert(rule, level);
+    }
+    pub fn level(&self, rule: Rule) -> Option<Severity> {
+        self.levels.get(&rule).copied().flatten()
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct LintDiagnostic {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for LintDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}:{}: {}[{}]: {}", self.line, self.column, severity, self.rule.id(), self.message)
+    }
+}
+
+struct Binding {
+    name: String,
+    line: usize,
+    column: usize,
+    used: bool,
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    scopes: Vec<Vec<Binding>>,
+    diagnostics: Vec<LintDiagnostic>,
+}
+
+pub(crate) fn position(node: &SyntaxNode) -> (usize, usize) {
+    match node {
+        SyntaxNode::Atom { line, column, .. } | SyntaxNode::List { line, column, .. } => (*line, *column),
+        SyntaxNode::Comment { .. } | SyntaxNode::BlankLine => (0, 0),
+    }
+}
+
+pub(crate) fn symbol(node: &SyntaxNode) -> Option<&str> {
+    match node {
+        SyntaxNode::Atom { text, .. } if !text.starts_with('"') && !text.chars().all(|c| c.is_ascii_digit()) => Some(text),
+        _ => None,
+    }
+}
+
+pub(crate) fn code(nodes: &[SyntaxNode]) -> Vec<&SyntaxNode> {
+    nodes.iter().filter(|node| matches!(node, SyntaxNode::Atom { .. } | SyntaxNode::List { .. })).collect()
+}
+
+pub(crate) fn list_items(node: &SyntaxNode) -> Option<Vec<&SyntaxNode>> {
+    match node {
+        SyntaxNode::List { items, .. } => Some(code(items)),
+        _ => None,
+    }
+}
+
+// A name may carry a type annotation, as in `(var (x : number) 1)` or `(def f ((x : number)) …)`.
+pub(crate) fn annotated_name(node: &SyntaxNode) -> &SyntaxNode {
+    match node {
+        SyntaxNode::List { items, .. } => code(items).first().copied().unwrap_or(node),
+        node => node,
+    }
+}
+
+fn atoms(node: &SyntaxNode) -> Vec<&SyntaxNode> {
+    match node {
+        SyntaxNode::List { items, .. } => items.iter().flat_map(atoms).collect(),
+        SyntaxNode::Atom { .. } => vec![node],
+        SyntaxNode::Comment { .. } | SyntaxNode::BlankLine => vec![],
+    }
+}
+
+// The atoms a `var` target, or a `match` pattern when `pattern` is set, binds.
+pub(crate) fn bound_names(node: &SyntaxNode, pattern: bool) -> Vec<&SyntaxNode> {
+    let names = match lower(node) {
+        Ok(Some(exp)) if pattern => Pattern::parse(&exp).map(|pattern| pattern.names()).unwrap_or_default(),
+        Ok(Some(exp)) => VarTarget::parse(&exp).map(|target| target.names()).unwrap_or_default(),
+        _ => vec![],
+    };
+    atoms(node).into_iter().filter(|atom| symbol(atom).is_some_and(|name| names.iter().any(|bound| bound == name))).collect()
+}
+
+// `if` and `begin` accept their parts either inline or wrapped in one extra list.
+pub(crate) fn wrapped_parts<'n>(args: &[&'n SyntaxNode]) -> Vec<&'n SyntaxNode> {
+    match args {
+        [only] => match list_items(only) {
+            Some(items) if items.first().is_some_and(|first| list_items(first).is_some()) => items,
+            _ => args.to_vec(),
+        },
+        _ => args.to_vec(),
+    }
+}
+
+// The condition and body of `(while (condition body))`, the only shape the runtime accepts.
+fn while_parts<'n>(items: &[&'n SyntaxNode]) -> Vec<&'n SyntaxNode> {
+    items.get(1).and_then(|wrapped| list_items(wrapped)).unwrap_or_default()
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, rule: Rule, node: &SyntaxNode, message: String) {
+        if let Some(severity) = self.config.level(rule) {
+            let (line, column) = position(node);
+            self.diagnostics.push(LintDiagnostic { rule, severity, message, line, column });
+        }
+    }
+    fn resolve(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes.iter_mut().rev().flat_map(|scope| scope.iter_mut().rev()).find(|binding| binding.name == name)
+    }
+    fn pop_scope(&mut self) {
+        for binding in self.scopes.pop().unwrap_or_default() {
+            if !binding.used && self.config.level(Rule::UnusedVariable).is_some() {
+                let node = SyntaxNode::Atom { text: binding.name.clone(), line: binding.line, column: binding.column };
+                self.report(Rule::UnusedVariable, &node, format!("variable `{}` is declared but never used", binding.name));
+            }
+        }
+    }
+    fn declare(&mut self, node: &SyntaxNode) {
+        if let Some(name) = symbol(node) {
+            let (line, column) = position(node);
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.push(Binding { name: name.to_owned(), line, column, used: false });
+            }
+        }
+    }
+    // `params [-> type] body…`: the parameters get their own scope around the body.
+    fn lint_function(&mut self, items: &[&SyntaxNode]) {
+        self.scopes.push(vec![]);
+        for param in list_items(items[0]).unwrap_or_default() {
+            self.declare(annotated_name(param));
+        }
+        let body = match items[1..] {
+            [arrow, _, ref body @ ..] if symbol(arrow) == Some("->") => body,
+            ref body => body,
+        };
+        self.lint_statements(body);
+        self.pop_scope();
+    }
+    fn lint_statements(&mut self, statements: &[&SyntaxNode]) {
+        for (index, statement) in statements.iter().enumerate() {
+            self.lint_expression(statement);
+            if let (true, Some(next)) = (Linter::is_infinite_loop(statement), statements.get(index + 1)) {
+                self.report(Rule::UnreachableCode, next, "unreachable code after `(while (true …))`".to_owned());
+            }
+        }
+    }
+    fn is_infinite_loop(node: &SyntaxNode) -> bool {
+        let items = match list_items(node) {
+            Some(items) if items.first().and_then(|head| symbol(head)) == Some("while") => items,
+            _ => return false,
+        };
+        matches!(while_parts(&items).first(), Some(SyntaxNode::Atom { text, .. }) if text == "true")
+    }
+    fn lint_expression(&mut self, node: &SyntaxNode) {
+        match node {
+            SyntaxNode::List { items, .. } => self.lint_form(node, &code(items)),
+            node => {
+                if let Some(name) = symbol(node) {
+                    if let Some(binding) = self.resolve(name) {
+                        binding.used = true;
+                    }
+                }
+            }
+        }
+    }
+    // `var` and `set` evaluate everything after the name as one expression, so `(var x begin (…))` is a block.
+    fn lint_value(&mut self, form: &SyntaxNode, items: &[&SyntaxNode]) {
+        match items {
+            [] => {}
+            [single] => self.lint_expression(single),
+            items => self.lint_form(form, items),
+        }
+    }
+    fn lint_form(&mut self, form: &SyntaxNode, items: &[&SyntaxNode]) {
+        let head = match items.first() {
+            Some(head) => head,
+            None => return,
+        };
+        match symbol(head) {
+            Some("begin") => {
+                self.scopes.push(vec![]);
+                self.lint_statements(&wrapped_parts(&items[1..]));
+                self.pop_scope();
+            }
+            Some("var" | "const") if items.len() >= 2 => {
+                self.lint_value(form, &items[2..]);
+                bound_names(items[1], false).into_iter().for_each(|name| self.declare(name));
+            }
+            Some("match") if items.len() >= 2 => {
+                self.lint_expression(items[1]);
+                for clause in &items[2..] {
+                    let parts = list_items(clause).unwrap_or_default();
+                    self.scopes.push(vec![]);
+                    if let Some(pattern) = parts.first() {
+                        bound_names(pattern, true).into_iter().for_each(|name| self.declare(name));
+                    }
+                    self.lint_statements(&parts[1.min(parts.len())..]);
+                    self.pop_scope();
+                }
+            }
+            Some(kind @ ("let" | "let*" | "letrec")) if items.len() >= 2 => {
+                let bindings: Vec<Vec<&SyntaxNode>> = list_items(items[1]).unwrap_or_default().into_iter().filter_map(list_items).collect();
+                if kind == "let" {
+                    bindings.iter().for_each(|binding| self.lint_value(form, &binding[1.min(binding.len())..]));
+                }
+                self.scopes.push(vec![]);
+                for binding in &bindings {
+                    if kind == "let*" {
+                        self.lint_value(form, &binding[1.min(binding.len())..]);
+                    }
+                    if let Some(name) = binding.first() {
+                        self.declare(name);
+                    }
+                }
+                if kind == "letrec" {
+                    bindings.iter().for_each(|binding| self.lint_value(form, &binding[1.min(binding.len())..]));
+                }
+                self.lint_statements(&items[2..]);
+                self.pop_scope();
+            }
+            Some("def") if items.len() >= 3 => {
+                self.declare(items[1]);
+                self.lint_function(&items[2..]);
+            }
+            Some("lambda") if items.len() >= 2 => self.lint_function(&items[1..]),
+            Some("set") if items.len() >= 2 => {
+                if let Some(name) = symbol(items[1]) {
+                    if self.resolve(name).is_none() {
+                        self.report(Rule::UndeclaredSet, items[1], format!("`set` on `{}`, which no enclosing `var` declares", name));
+                    }
+                }
+                self.lint_value(form, &items[2..]);
+            }
+            Some("if") => {
+                let parts = match &items[1..] {
+                    [only] => list_items(only).filter(|parts| parts.len() >= 2).unwrap_or_else(|| items[1..].to_vec()),
+                    parts => parts.to_vec(),
+                };
+                if parts.len() < 2 || parts.len() > 3 {
+                    self.report(Rule::IfArity, form, format!("`if` needs a condition, a branch and an optional else branch, found {} parts", parts.len()));
+                }
+                self.lint_statements(&parts);
+            }
+            Some("while") => self.lint_statements(&while_parts(items)),
+            Some("+") => {
+                let literal_kinds: Vec<&str> = items[1..].iter().filter_map(|item| match item {
+                    SyntaxNode::Atom { text, .. } if text.starts_with('"') => Some("string"),
+                    SyntaxNode::Atom { text, .. } if text.chars().all(|c| c.is_ascii_digit()) => Some("number"),
+                    _ => None,
+                }).collect();
+                if literal_kinds.contains(&"string") && literal_kinds.contains(&"number") {
+                    self.report(Rule::StringNumberAdd, form, "`+` on a string and a number literal".to_owned());
+                }
+                self.lint_statements(&items[1..]);
+            }
+            _ => self.lint_statements(items),
+        }
+    }
+}
+
+pub fn lint_nodes(nodes: &[SyntaxNode], config: &LintConfig) -> Vec<LintDiagnostic> {
+    let globals = get_environment_manager().borrow().visible_names().into_iter()
+        .map(|name| Binding { name, line: 0, column: 0, used: true })
+        .collect();
+    let mut linter = Linter { config, scopes: vec![globals, vec![]], diagnostics: vec![] };
+    linter.lint_statements(&code(nodes));
+    linter.pop_scope();
+    linter.diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    linter.diagnostics
+}
+
+fn parse_level(flag: &str) -> Option<Option<Severity>> {
+    match flag {
+        "--allow" => Some(None),
+        "--warn" => Some(Some(Severity::Warning)),
+        "--deny" => Some(Some(Severity::Error)),
+        _ => None,
+    }
+}
+
+/// `eva lint [--allow|--warn|--deny <rule>]… files…`: exits 1 when any error-level rule fires.
+pub fn run(args: &[String]) -> i32 {
+    let mut config = LintConfig::default();
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match parse_level(arg) {
+            Some(level) => match args.next().and_then(|id| Rule::from_id(id)) {
+                Some(rule) => config.set(rule, level),
+                None => {
+                    eprintln!("{} expects one of: {}", arg, RULES.iter().map(Rule::id).collect::<Vec<&str>>().join(", "));
+                    return 2;
+                }
+            },
+            None => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("usage: eva lint [--allow|--warn|--deny <rule>]... <files>...");
+        return 2;
+    }
+    let mut status = 0;
+    for file in files {
+        let nodes = match fs::read_to_string(file).map_err(|error| error.to_string())
+            .and_then(|source| parse_syntax(&source).map_err(|error| error.to_string())) {
+            Ok(nodes) => nodes,
+            Err(error) => {
+                eprintln!("{}: {}", file, error);
+                status = 2;
+                continue;
+            }
+        };
+        for diagnostic in lint_nodes(&nodes, &config) {
+            println!("{}:{}", file, diagnostic);
+            if diagnostic.severity == Severity::Error {
+                status = status.max(1);
+            }
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::Severity;
+    use crate::lint::{lint_nodes, LintConfig, Rule};
+    use crate::parser::parse_syntax;
+
+    fn lint(source: &str, config: &LintConfig) -> Vec<(Rule, usize, usize)> {
+        lint_nodes(&parse_syntax(source).unwrap(), config).into_iter()
+            .map(|diagnostic| (diagnostic.rule, diagnostic.line, diagnostic.column))
+            .collect()
+    }
+
+    #[test]
+    fn test_undeclared_set() {
+        let source = "(var total 0)\n(begin\n  (set total 1)\n  (set totl 2))\ntotal\n";
+        assert_eq!(lint(source, &LintConfig::default()), vec![(Rule::UndeclaredSet, 4, 8)]);
+    }
+
+    #[test]
+    fn test_if_arity() {
+        let source = "(var x 1)\n(if ((> x 1)))\n(if (> x 1) x x)\n(if ((> x 1) x x))\n(if (> x 1) x)\n(if (> x 1) x x x)\n";
+        assert_eq!(lint(source, &LintConfig::default()), vec![(Rule::IfArity, 2, 1), (Rule::IfArity, 6, 1)]);
+    }
+
+    #[test]
+    fn test_let_bindings() {
+        let source = "(let ((x 1) (y 2))\n  (set x y)\n  x)\n(let* ((a 1) (b a)) (set b 2) b)\n(set x 3)\n";
+        assert_eq!(lint(source, &LintConfig::default()), vec![(Rule::UndeclaredSet, 5, 6)]);
+    }
+
+    #[test]
+    fn test_patterns() {
+        let source = "(var (head . tail) (split \"a,b\" \",\"))\n(match head\n  ((map \"k\" v) v)\n  (other when (> 1 0) tail))\n";
+        assert_eq!(lint(source, &LintConfig::default()), vec![(Rule::UnusedVariable, 4, 4)]);
+    }
+
+    #[test]
+    fn test_unused_variable() {
+        let source = "(begin\n  (var used 1)\n  (var unused 2)\n  (var result begin ((var inner used) inner))\n  result)\n";
+        assert_eq!(lint(source, &LintConfig::default()), vec![(Rule::UnusedVariable, 3, 8)]);
+    }
+
+    #[test]
+    fn test_unreachable_after_infinite_loop() {
+        let source = "(var x 0)\n(begin\n  (while (true (set x (+ x 1))))\n  x)\n";
+        assert_eq!(lint(source, &LintConfig::default()), vec![(Rule::UnreachableCode, 4, 3)]);
+    }
+
+    #[test]
+    fn test_string_number_add() {
+        let source = "(+ \"a\" 1)\n(+ \"a\" \"b\")\n";
+        assert_eq!(lint(source, &LintConfig::default()), vec![(Rule::StringNumberAdd, 1, 1)]);
+    }
+
+    #[test]
+    fn test_functions_and_annotations() {
+        let source = "(var (total : number) 0)\n(def add ((x : number) unused) -> number\n  (+ x total))\n(add 1 2)\n(lambda (y) y)\n";
+        assert_eq!(lint(source, &LintConfig::default()), vec![(Rule::UnusedVariable, 2, 24)]);
+    }
+
+    #[test]
+    fn test_rules_are_configurable() {
+        let source = "(var unused 1)\n(+ \"a\" 1)\n";
+        let mut config = LintConfig::default();
+        config.set(Rule::UnusedVariable, None);
+        config.set(Rule::StringNumberAdd, Some(Severity::Warning));
+        let diagnostics = lint_nodes(&parse_syntax(source).unwrap(), &config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].rule, diagnostics[0].severity), (Rule::StringNumberAdd, Severity::Warning));
+        assert_eq!(diagnostics[0].to_string(), "2:1: warning[string-number-add]: `+` on a string and a number literal");
+    }
+}
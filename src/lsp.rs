@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use serde_json::{json, Value};
+use crate::dap::{read_message, write_message};
+use crate::formatter::format_source;
+use crate::function::Signature;
+use crate::lint::{annotated_name, bound_names, code, list_items, position, symbol, wrapped_parts};
+use crate::parser::{lower, parse, parse_syntax, ParseError, SyntaxNode};
+use crate::type_checker::TypeChecker;
+use crate::types::Type;
+use crate::{fs_library, get_environment_manager, io_library, json_library, optimizer, string_library, system_library, type_library, EvalType};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+const REQUEST_FAILED: i64 = -32803;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Kind {
+    Variable,
+    Constant,
+    Function,
+    Parameter,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Variable => "variable",
+            Kind::Constant => "constant",
+            Kind::Function => "function",
+            Kind::Parameter => "parameter",
+        }
+    }
+    // The `CompletionItemKind` of the protocol.
+    fn completion_kind(&self) -> u64 {
+        match self {
+            Kind::Variable | Kind::Parameter => 6,
+            Kind::Constant => 21,
+            Kind::Function => 3,
+        }
+    }
+}
+
+/// A name some form binds, with every symbol that resolves to it.
+struct Definition {
+    name: String,
+    line: usize,
+    column: usize,
+    kind: Kind,
+    detail: Type,
+    references: Vec<(usize, usize)>,
+}
+
+impl Definition {
+    fn spans(&self, line: usize, column: usize) -> bool {
+        let length = self.name.chars().count();
+        std::iter::once(&(self.line, self.column)).chain(&self.references)
+            .any(|&(start_line, start)| start_line == line && start <= column && column <= start + length)
+    }
+}
+
+// The part of the source where some bindings are visible: the document, or a form that opens a scope, up to and
+// including its closing `)`.
+struct Scope {
+    start: (usize, usize),
+    end: (usize, usize),
+    bindings: Vec<usize>,
+}
+
+/// The bindings of one document and where each is visible, positions in the parser's 1-based lines and columns.
+struct Analysis {
+    definitions: Vec<Definition>,
+    scopes: Vec<Scope>,
+}
+
+impl Analysis {
+    fn new(source: &str) -> Result<Analysis, ParseError> {
+        parse(source)?;
+        let nodes = parse_syntax(source)?;
+        let document = Scope { start: (0, 0), end: (usize::MAX, usize::MAX), bindings: vec![] };
+        let mut resolver = Resolver {
+            ends: closing_parens(source), definitions: vec![], scopes: vec![], open: vec![document], functions: vec![], pending: vec![],
+        };
+        resolver.statements(&code(&nodes));
+        resolver.pop_scope();
+        resolver.resolve_pending();
+        Ok(Analysis { definitions: resolver.definitions, scopes: resolver.scopes })
+    }
+    fn definition_at(&self, line: usize, column: usize) -> Option<&Definition> {
+        self.definitions.iter().find(|definition| definition.spans(line, column))
+    }
+    // Innermost first, and only what is declared before the position.
+    fn visible_at(&self, line: usize, column: usize) -> Vec<&Definition> {
+        let mut scopes: Vec<&Scope> = self.scopes.iter().filter(|scope| scope.start < (line, column) && (line, column) <= scope.end).collect();
+        scopes.sort_by_key(|scope| std::cmp::Reverse(scope.start));
+        let mut visible: Vec<&Definition> = vec![];
+        for index in scopes.iter().flat_map(|scope| scope.bindings.iter().rev()) {
+            let definition = &self.definitions[*index];
+            if (definition.line, definition.column) < (line, column) && visible.iter().all(|seen| seen.name != definition.name) {
+                visible.push(definition);
+            }
+        }
+        visible
+    }
+}
+
+// Where each `(` is closed, keyed by the position of the `(`.
+fn closing_parens(source: &str) -> HashMap<(usize, usize), (usize, usize)> {
+    let (mut ends, mut open) = (HashMap::new(), vec![]);
+    let (mut line, mut column) = (1, 1);
+    let (mut in_string, mut in_comment, mut escaped) = (false, false, false);
+    for c in source.chars() {
+        match c {
+            _ if in_comment => in_comment = c != '\n',
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            ';' => in_comment = true,
+            '(' => open.push((line, column)),
+            ')' => {
+                if let Some(start) = open.pop() {
+                    ends.insert(start, (line, column));
+                }
+            }
+            _ => {}
+        }
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    ends
+}
+
+// A name a function body uses before it is declared, with the starts of the scopes around the function.
+struct Pending {
+    name: String,
+    at: (usize, usize),
+    scopes: Vec<(usize, usize)>,
+}
+
+/// Walks a program with the scoping rules of `EnvironmentManagerRaw`, the ones `lint` follows too: `begin`,
+/// functions, `let` and `match` clauses open a scope, and `var` binds its name only after its value.
+struct Resolver {
+    ends: HashMap<(usize, usize), (usize, usize)>,
+    definitions: Vec<Definition>,
+    scopes: Vec<Scope>,
+    open: Vec<Scope>,
+    // How many scopes were open outside each function being walked.
+    functions: Vec<usize>,
+    pending: Vec<Pending>,
+}
+
+impl Resolver {
+    fn declare(&mut self, node: &SyntaxNode, kind: Kind, detail: Type) {
+        if let Some(name) = symbol(node) {
+            let (line, column) = position(node);
+            self.definitions.push(Definition { name: name.to_owned(), line, column, kind, detail, references: vec![] });
+            let index = self.definitions.len() - 1;
+            if let Some(scope) = self.open.last_mut() {
+                scope.bindings.push(index);
+            }
+        }
+    }
+    fn resolve(&mut self, node: &SyntaxNode) {
+        let found = symbol(node).and_then(|name| {
+            self.open.iter().rev().flat_map(|scope| scope.bindings.iter().rev()).copied().find(|index| self.definitions[*index].name == name)
+        });
+        match (found, symbol(node), self.functions.last()) {
+            (Some(index), _, _) => self.definitions[index].references.push(position(node)),
+            // A body only runs once called, by which time the scopes around it may have declared the name.
+            (None, Some(name), Some(&outside)) => {
+                let scopes = self.open[..outside].iter().rev().map(|scope| scope.start).collect();
+                self.pending.push(Pending { name: name.to_owned(), at: position(node), scopes });
+            }
+            _ => {}
+        }
+    }
+    // Resolves the pending names against the whole of their scopes, once every scope is closed.
+    fn resolve_pending(&mut self) {
+        for Pending { name, at, scopes } in std::mem::take(&mut self.pending) {
+            let found = scopes.iter().filter_map(|start| self.scopes.iter().find(|scope| scope.start == *start))
+                .find_map(|scope| scope.bindings.iter().rev().copied().find(|index| self.definitions[*index].name == name));
+            if let Some(index) = found {
+                self.definitions[index].references.push(at);
+            }
+        }
+        self.definitions.iter_mut().for_each(|definition| definition.references.sort());
+    }
+    fn push_scope(&mut self, node: &SyntaxNode) {
+        let start = position(node);
+        let end = self.ends.get(&start).copied().unwrap_or(start);
+        self.open.push(Scope { start, end, bindings: vec![] });
+    }
+    fn pop_scope(&mut self) {
+        self.scopes.extend(self.open.pop());
+    }
+    // Checks `node` knowing the types of everything visible; the checker is returned for the names it declared.
+    fn check(&self, node: &SyntaxNode) -> (TypeChecker, Type) {
+        let scope = self.open.iter().flat_map(|scope| &scope.bindings)
+            .map(|index| (self.definitions[*index].name.clone(), self.definitions[*index].detail.clone()))
+            .collect();
+        let mut checker = TypeChecker::new().with_scope(scope);
+        let result = match lower(node) {
+            Ok(Some(exp)) => checker.check(&exp),
+            _ => Type::Any,
+        };
+        (checker, result)
+    }
+    fn infer(&self, value: &[&SyntaxNode]) -> Type {
+        match value {
+            [single] => self.check(single).1,
+            _ => Type::Any,
+        }
+    }
+    // `params [-> type] body…`, scoped to `form`.
+    fn function(&mut self, form: &SyntaxNode, items: &[&SyntaxNode]) {
+        self.functions.push(self.open.len());
+        self.push_scope(form);
+        for param in list_items(items[0]).unwrap_or_default() {
+            let annotation = match lower(param) {
+                Ok(Some(param)) => Signature::parse_parameter(&param).ok().and_then(|param| param.annotation),
+                _ => None,
+            };
+            self.declare(annotated_name(param), Kind::Parameter, annotation.unwrap_or(Type::Any));
+        }
+        let body = match items[1..] {
+            [arrow, _, ref body @ ..] if symbol(arrow) == Some("->") => body,
+            ref body => body,
+        };
+        self.statements(body);
+        self.pop_scope();
+        self.functions.pop();
+    }
+    fn statements(&mut self, statements: &[&SyntaxNode]) {
+        statements.iter().for_each(|statement| self.expression(statement));
+    }
+    fn expression(&mut self, node: &SyntaxNode) {
+        match node {
+            SyntaxNode::List { items, .. } => self.form(node, &code(items)),
+            node => self.resolve(node),
+        }
+    }
+    fn value(&mut self, form: &SyntaxNode, items: &[&SyntaxNode]) {
+        match items {
+            [] => {}
+            [single] => self.expression(single),
+            items => self.form(form, items),
+        }
+    }
+    fn form(&mut self, form: &SyntaxNode, items: &[&SyntaxNode]) {
+        let head = match items.first() {
+            Some(head) => head,
+            None => return,
+        };
+        match symbol(head) {
+            Some("begin") => {
+                self.push_scope(form);
+                self.statements(&wrapped_parts(&items[1..]));
+                self.pop_scope();
+            }
+            // An annotated name or a pattern has the type the checker declared it with; a plain name that of its value.
+            Some(keyword @ ("var" | "const")) if items.len() >= 2 => {
+                self.value(form, &items[2..]);
+                let (checker, value) = self.check(form);
+                let plain = matches!(lower(items[1]), Ok(Some(EvalType::Value(_))));
+                let kind = if keyword == "const" { Kind::Constant } else { Kind::Variable };
+                for name in bound_names(items[1], false) {
+                    let detail = match plain {
+                        true => value.clone(),
+                        false => symbol(name).and_then(|name| checker.lookup(name)).unwrap_or(Type::Any),
+                    };
+                    self.declare(name, kind, detail);
+                }
+            }
+            Some("match") if items.len() >= 2 => {
+                self.expression(items[1]);
+                for clause in &items[2..] {
+                    let parts = list_items(clause).unwrap_or_default();
+                    self.push_scope(clause);
+                    if let Some(pattern) = parts.first() {
+                        bound_names(pattern, true).into_iter().for_each(|name| self.declare(name, Kind::Variable, Type::Any));
+                    }
+                    self.statements(&parts[1.min(parts.len())..]);
+                    self.pop_scope();
+                }
+            }
+            Some(kind @ ("let" | "let*" | "letrec")) if items.len() >= 2 => {
+                let bindings: Vec<Vec<&SyntaxNode>> = list_items(items[1]).unwrap_or_default().into_iter().filter_map(list_items).collect();
+                if kind == "let" {
+                    bindings.iter().for_each(|binding| self.value(form, &binding[1.min(binding.len())..]));
+                }
+                let types: Vec<Type> = bindings.iter().map(|binding| self.infer(&binding[1.min(binding.len())..])).collect();
+                self.push_scope(form);
+                for (binding, detail) in bindings.iter().zip(types) {
+                    if kind == "let*" {
+                        self.value(form, &binding[1.min(binding.len())..]);
+                    }
+                    if let Some(name) = binding.first() {
+                        self.declare(name, Kind::Variable, detail);
+                    }
+                }
+                if kind == "letrec" {
+                    bindings.iter().for_each(|binding| self.value(form, &binding[1.min(binding.len())..]));
+                }
+                self.statements(&items[2..]);
+                self.pop_scope();
+            }
+            Some("def") if items.len() >= 3 => {
+                let detail = self.check(form).1;
+                self.declare(items[1], Kind::Function, detail);
+                self.function(form, &items[2..]);
+            }
+            Some("lambda") if items.len() >= 2 => self.function(form, &items[1..]),
+            _ => self.statements(items),
+        }
+    }
+}
+
+/// Everything completion offers besides the document's own bindings: special forms, then built-ins and globals.
+fn built_in_names() -> Vec<(String, u64)> {
+    let keywords = optimizer::SPECIAL_FORMS.iter().filter(|form| form.chars().all(|c| c.is_alphabetic() || c == '*')).map(|form| (form.to_string(), 14));
+    let builtins = [&string_library::STRING_BUILTINS[..], &type_library::TYPE_BUILTINS, &json_library::JSON_BUILTINS, &io_library::IO_BUILTINS,
+        &fs_library::FS_BUILTINS, &system_library::SYSTEM_BUILTINS, &["gc"]];
+    let builtins = builtins.into_iter().flatten().map(|name| (name.to_string(), 3));
+    let globals = get_environment_manager().borrow().visible_names().into_iter().map(|name| (name, 6));
+    keywords.chain(builtins).chain(globals).collect()
+}
+
+/// One open file. The analysis is that of the last version that parsed, so completion keeps working while an
+/// edit leaves a `(` unclosed.
+struct Document {
+    text: String,
+    analysis: Option<Analysis>,
+}
+
+impl Document {
+    fn line(&self, line: usize) -> &str {
+        self.text.split('\n').nth(line.saturating_sub(1)).unwrap_or_default()
+    }
+    // Protocol positions count lines from 0 and characters in UTF-16 code units.
+    fn position(&self, position: &Value) -> (usize, usize) {
+        let line = position["line"].as_u64().unwrap_or(0) as usize + 1;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+        let mut units = 0;
+        let column = self.line(line).chars().take_while(|c| {
+            units += c.len_utf16();
+            units <= character
+        }).count();
+        (line, column + 1)
+    }
+    fn to_protocol(&self, line: usize, column: usize) -> Value {
+        let character: usize = self.line(line).chars().take(column.saturating_sub(1)).map(char::len_utf16).sum();
+        json!({ "line": line.saturating_sub(1), "character": character })
+    }
+    // `length` is in UTF-16 code units too.
+    fn range(&self, line: usize, column: usize, length: usize) -> Value {
+        let start = self.to_protocol(line, column);
+        let end = json!({ "line": start["line"], "character": start["character"].as_u64().unwrap_or(0) as usize + length });
+        json!({ "start": start, "end": end })
+    }
+}
+
+struct Server {
+    output: Box<dyn Write>,
+    documents: HashMap<String, Document>,
+    shutting_down: bool,
+}
+
+/// Serves one editor session until `exit`, returning the exit code the protocol asks for: 0 only after `shutdown`.
+pub fn serve(mut input: impl BufRead, output: impl Write + 'static) -> i32 {
+    let mut server = Server { output: Box::new(output), documents: HashMap::new(), shutting_down: false };
+    loop {
+        let message = match read_message(&mut input) {
+            Some(message) => message,
+            None => return 1,
+        };
+        let method = message["method"].as_str().unwrap_or_default();
+        if method == "exit" {
+            return if server.shutting_down { 0 } else { 1 };
+        }
+        match message.get("id") {
+            Some(id) => {
+                let reply = match server.request(method, &message["params"]) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, error)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": error } }),
+                };
+                write_message(&mut server.output, &reply);
+            }
+            None => server.notification(method, &message["params"]),
+        }
+    }
+}
+
+impl Server {
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        if self.shutting_down {
+            return Err((INVALID_REQUEST, "the server is shutting down".to_owned()));
+        }
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "documentFormattingProvider": true,
+                },
+                "serverInfo": { "name": "eva" },
+            })),
+            "shutdown" => {
+                self.shutting_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => Ok(self.definition(params).map_or(Value::Null, |(document, definition)| {
+                json!({ "uri": params["textDocument"]["uri"], "range": document.range(definition.line, definition.column, definition.name.chars().map(char::len_utf16).sum()) })
+            })),
+            "textDocument/references" => Ok(self.references(params)),
+            "textDocument/hover" => Ok(self.definition(params).map_or(Value::Null, |(_, definition)| {
+                json!({ "contents": { "kind": "plaintext", "value": format!("{} {}: {}", definition.kind.name(), definition.name, definition.detail) } })
+            })),
+            "textDocument/completion" => Ok(self.completion(params)),
+            "textDocument/formatting" => self.formatting(params),
+            method => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+    fn notification(&mut self, method: &str, params: &Value) {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_owned();
+        match method {
+            "textDocument/didOpen" => self.update(uri, params["textDocument"]["text"].as_str().unwrap_or_default().to_owned()),
+            // Documents are synchronised in full, so the last change holds the whole text.
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array().cloned().unwrap_or_default();
+                if let Some(text) = changes.last().and_then(|change| change["text"].as_str()) {
+                    self.update(uri, text.to_owned());
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish(&uri, vec![]);
+            }
+            _ => {}
+        }
+    }
+    fn update(&mut self, uri: String, text: String) {
+        let previous = self.documents.remove(&uri).and_then(|document| document.analysis);
+        let mut document = Document { text, analysis: None };
+        let diagnostics = match Analysis::new(&document.text) {
+            Ok(analysis) => {
+                document.analysis = Some(analysis);
+                vec![]
+            }
+            Err(error) => {
+                document.analysis = previous;
+                vec![json!({ "range": document.range(error.line, error.column, 0), "severity": 1, "source": "eva", "message": error.message })]
+            }
+        };
+        self.documents.insert(uri.clone(), document);
+        self.publish(&uri, diagnostics);
+    }
+    fn publish(&mut self, uri: &str, diagnostics: Vec<Value>) {
+        let notification = json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": { "uri": uri, "diagnostics": diagnostics } });
+        write_message(&mut self.output, &notification);
+    }
+    fn document(&self, params: &Value) -> Option<&Document> {
+        self.documents.get(params["textDocument"]["uri"].as_str()?)
+    }
+    fn definition(&self, params: &Value) -> Option<(&Document, &Definition)> {
+        let document = self.document(params)?;
+        let (line, column) = document.position(&params["position"]);
+        Some((document, document.analysis.as_ref()?.definition_at(line, column)?))
+    }
+    fn references(&self, params: &Value) -> Value {
+        let (document, definition) = match self.definition(params) {
+            Some(found) => found,
+            None => return json!([]),
+        };
+        let declaration = params["context"]["includeDeclaration"].as_bool().unwrap_or(true).then_some((definition.line, definition.column));
+        let locations: Vec<Value> = declaration.into_iter().chain(definition.references.iter().copied())
+            .map(|(line, column)| json!({ "uri": params["textDocument"]["uri"], "range": document.range(line, column, definition.name.chars().map(char::len_utf16).sum()) }))
+            .collect();
+        json!(locations)
+    }
+    fn completion(&self, params: &Value) -> Value {
+        let document = match self.document(params) {
+            Some(document) => document,
+            None => return json!([]),
+        };
+        let (line, column) = document.position(&params["position"]);
+        let visible = document.analysis.as_ref().map_or(vec![], |analysis| analysis.visible_at(line, column));
+        let mut items: Vec<Value> = visible.iter()
+            .map(|definition| json!({ "label": definition.name, "kind": definition.kind.completion_kind(), "detail": definition.detail.to_string() }))
+            .collect();
+        for (name, kind) in built_in_names() {
+            if visible.iter().all(|definition| definition.name != name) && items.iter().all(|item| item["label"] != name.as_str()) {
+                items.push(json!({ "label": name, "kind": kind }));
+            }
+        }
+        json!(items)
+    }
+    fn formatting(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let document = match self.document(params) {
+            Some(document) => document,
+            None => return Ok(json!([])),
+        };
+        let formatted = format_source(&document.text).map_err(|error| (REQUEST_FAILED, error.to_string()))?;
+        if formatted == document.text {
+            return Ok(json!([]));
+        }
+        let last = document.text.split('\n').count();
+        let end = document.to_protocol(last, document.line(last).chars().count() + 1);
+        Ok(json!([{ "range": { "start": { "line": 0, "character": 0 }, "end": end }, "newText": formatted }]))
+    }
+}
+
+/// `eva lsp`: a language server on standard input and output.
+pub fn run(args: &[String]) -> i32 {
+    if !args.is_empty() {
+        eprintln!("usage: eva lsp");
+        return 2;
+    }
+    serve(io::stdin().lock(), io::stdout())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use crate::dap::{read_message, write_message};
+    use crate::io_library::Capture;
+    use crate::lsp::serve;
+
+    const URI: &str = "file:///tmp/totals.eva";
+    const PROGRAM: &str = "(var total 0)
+(def add ((x : number)) -> number
+  (begin
+    (var total (+ x 1))
+    total))
+(set total (add 41))
+(var label \"sum\")
+";
+
+    /// Scripts a session the way an editor would drive it, then runs the server over it in-process.
+    struct Client {
+        script: Vec<u8>,
+        id: u64,
+    }
+
+    impl Client {
+        fn new() -> Client {
+            let mut client = Client { script: vec![], id: 0 };
+            client.request("initialize", json!({ "capabilities": {} }));
+            client.notify("initialized", json!({}));
+            client
+        }
+        fn request(&mut self, method: &str, params: Value) -> u64 {
+            self.id += 1;
+            write_message(&mut self.script, &json!({ "jsonrpc": "2.0", "id": self.id, "method": method, "params": params }));
+            self.id
+        }
+        fn notify(&mut self, method: &str, params: Value) {
+            write_message(&mut self.script, &json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+        }
+        fn at(&mut self, method: &str, line: u64, character: u64) -> u64 {
+            self.request(method, json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } }))
+        }
+        fn finish(mut self) -> Session {
+            self.request("shutdown", json!(null));
+            self.notify("exit", json!(null));
+            let output = Capture::new();
+            let status = serve(self.script.as_slice(), output.clone());
+            let text = output.text();
+            let mut replies = text.as_bytes();
+            Session { status, messages: std::iter::from_fn(|| read_message(&mut replies)).collect() }
+        }
+    }
+
+    struct Session {
+        status: i32,
+        messages: Vec<Value>,
+    }
+
+    impl Session {
+        fn result(&self, id: u64) -> &Value {
+            &self.messages.iter().find(|message| message["id"] == id).unwrap()["result"]
+        }
+        fn diagnostics(&self) -> Vec<&Value> {
+            self.messages.iter().filter(|message| message["method"] == "textDocument/publishDiagnostics").map(|message| &message["params"]["diagnostics"]).collect()
+        }
+    }
+
+    fn open(client: &mut Client, text: &str) {
+        client.notify("textDocument/didOpen", json!({ "textDocument": { "uri": URI, "languageId": "eva", "version": 1, "text": text } }));
+    }
+
+    fn range(line: u64, character: u64, length: u64) -> Value {
+        json!({ "start": { "line": line, "character": character }, "end": { "line": line, "character": character + length } })
+    }
+
+    #[test]
+    fn test_definition_and_references() {
+        let mut client = Client::new();
+        open(&mut client, PROGRAM);
+        let inner = client.at("textDocument/definition", 4, 6);
+        let call = client.at("textDocument/definition", 5, 12);
+        let outer = client.at("textDocument/references", 0, 7);
+        let undefined = client.at("textDocument/definition", 3, 16);
+        let session = client.finish();
+        assert_eq!(session.result(inner), &json!({ "uri": URI, "range": range(3, 9, 5) }));
+        assert_eq!(session.result(call), &json!({ "uri": URI, "range": range(1, 5, 3) }));
+        // The `total` inside `add` is a different binding, so only the `set` refers to the global one.
+        assert_eq!(session.result(outer), &json!([{ "uri": URI, "range": range(0, 5, 5) }, { "uri": URI, "range": range(5, 5, 5) }]));
+        assert_eq!(session.result(undefined), &json!(null));
+        assert_eq!(session.status, 0);
+    }
+
+    #[test]
+    fn test_names_declared_after_a_function() {
+        let mut client = Client::new();
+        open(&mut client, "(def f () later)\n(var later 5)\n(def ev (n) (if (== n 0) true (od (- n 1))))\n(def od (n) (if (== n 0) false (ev (- n 1))))\n(var early later)\n");
+        let later = client.at("textDocument/definition", 0, 11);
+        let od = client.at("textDocument/definition", 2, 32);
+        let ev = client.at("textDocument/references", 2, 6);
+        let session = client.finish();
+        assert_eq!(session.result(later), &json!({ "uri": URI, "range": range(1, 5, 5) }));
+        assert_eq!(session.result(od), &json!({ "uri": URI, "range": range(3, 5, 2) }));
+        assert_eq!(session.result(ev), &json!([{ "uri": URI, "range": range(2, 5, 2) }, { "uri": URI, "range": range(3, 32, 2) }]));
+    }
+
+    #[test]
+    fn test_ranges_count_utf16_units() {
+        let mut client = Client::new();
+        open(&mut client, "(var é😀 1)\n(+ é😀 é😀)\n");
+        let references = client.at("textDocument/references", 1, 8);
+        let session = client.finish();
+        assert_eq!(session.result(references), &json!([{ "uri": URI, "range": range(0, 5, 3) }, { "uri": URI, "range": range(1, 3, 3) }, { "uri": URI, "range": range(1, 7, 3) }]));
+    }
+
+    #[test]
+    fn test_hover_and_completion() {
+        let mut client = Client::new();
+        open(&mut client, PROGRAM);
+        let hovers: Vec<u64> = [(1, 6), (3, 18), (4, 4), (6, 7)].into_iter().map(|(line, character)| client.at("textDocument/hover", line, character)).collect();
+        let completion = client.at("textDocument/completion", 4, 4);
+        let session = client.finish();
+        let hovers: Vec<&Value> = hovers.into_iter().map(|id| &session.result(id)["contents"]["value"]).collect();
+        assert_eq!(hovers, vec!["function add: (function (number) number)", "parameter x: number", "variable total: number", "variable label: string"]);
+        let items = session.result(completion).as_array().unwrap();
+        let item = |label: &str| items.iter().find(|item| item["label"] == label);
+        assert_eq!(item("total").map(|item| &item["detail"]), Some(&json!("number")));
+        assert_eq!(item("x").map(|item| &item["kind"]), Some(&json!(6)));
+        assert!(item("add").is_some() && item("str-len").is_some() && item("letrec").is_some());
+        assert!(item("label").is_none(), "`label` is declared after the cursor");
+    }
+
+    #[test]
+    fn test_diagnostics_and_formatting() {
+        let mut client = Client::new();
+        open(&mut client, "(var   x\n  (+ 1 2)");
+        let broken = client.request("textDocument/formatting", json!({ "textDocument": { "uri": URI }, "options": { "tabSize": 2, "insertSpaces": true } }));
+        client.notify("textDocument/didChange", json!({ "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": "(var   x 1)" }] }));
+        let formatting = client.request("textDocument/formatting", json!({ "textDocument": { "uri": URI }, "options": { "tabSize": 2, "insertSpaces": true } }));
+        let unknown = client.request("textDocument/rename", json!({}));
+        let session = client.finish();
+        assert_eq!(session.diagnostics(), vec![
+            &json!([{ "range": range(1, 9, 0), "severity": 1, "source": "eva", "message": "unclosed `(`" }]),
+            &json!([]),
+        ]);
+        let error = |id: u64| &session.messages.iter().find(|message| message["id"] == id).unwrap()["error"];
+        assert_eq!(error(broken)["message"], "2:10: unclosed `(`");
+        assert_eq!(session.result(formatting), &json!([{ "range": range(0, 0, 11), "newText": "(var x 1)\n" }]));
+        assert_eq!(error(unknown)["code"], -32601);
+    }
+}
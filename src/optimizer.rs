@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+use crate::function::Signature;
+use crate::pattern::{clause_parts, Pattern, VarTarget};
+use crate::{fs_library, io_library, json_library, string_library, system_library, type_library, Eva, EvalDataType, EvalType, LetBinding, Span};
+
+pub(crate) const SPECIAL_FORMS: [&str; 21] = [
+    "+", "*", "/", ">", "<", "??", "var", "const", "set", "if", "when", "unless", "cond", "while", "begin", "let", "let*", "letrec",
+    "match", "def", "lambda",
+];
+
+fn is_quoted(text: &str) -> bool {
+    text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'')
+}
+
+fn symbol(name: &str) -> EvalType {
+    EvalType::Value(EvalDataType::String(name.to_owned()))
+}
+
+// The runtime value of a literal the optimizer may fold: a number, a boolean, null or a `'text'` string.
+fn literal(exp: &EvalType) -> Option<EvalDataType> {
+    match exp {
+        EvalType::Value(value @ (EvalDataType::Number(_) | EvalDataType::Bool(_) | EvalDataType::Null)) => Some(value.clone()),
+        EvalType::Value(EvalDataType::String(text)) if is_quoted(text) => Some(EvalDataType::String(text[1..text.len() - 1].to_owned())),
+        _ => None,
+    }
+}
+
+fn to_literal(value: EvalDataType) -> Option<EvalType> {
+    match value {
+        EvalDataType::Number(_) | EvalDataType::Bool(_) | EvalDataType::Null => Some(EvalType::Value(value)),
+        EvalDataType::String(text) => Some(EvalType::Value(EvalDataType::String(format!("'{}'", text)))),
+        _ => None,
+    }
+}
+
+// Overflow and division by zero are left for `Eva::eval` to report at run time.
+fn fold_arithmetic(operator: &str, first: EvalDataType, second: EvalDataType) -> Option<EvalType> {
+    match (operator, first, second) {
+        ("+", EvalDataType::Number(a), EvalDataType::Number(b)) => a.checked_add(b).map(EvalDataType::Number),
+        ("+", EvalDataType::String(a), EvalDataType::String(b)) => Some(EvalDataType::String(format!("{}{}", a, b))),
+        ("*", EvalDataType::Number(a), EvalDataType::Number(b)) => a.checked_mul(b).map(EvalDataType::Number),
+        ("/", EvalDataType::Number(a), EvalDataType::Number(b)) => a.checked_div(b).map(EvalDataType::Number),
+        (">", EvalDataType::Number(a), EvalDataType::Number(b)) => Some(EvalDataType::Bool(a > b)),
+        ("<", EvalDataType::Number(a), EvalDataType::Number(b)) => Some(EvalDataType::Bool(a < b)),
+        _ => None,
+    }.and_then(to_literal)
+}
+
+// Names a scope declares anywhere in its own environment, i.e. outside nested `begin`, `def` and `lambda` bodies.
+fn collect_declarations(exp: &EvalType, names: &mut Vec<String>) {
+    let items = match exp {
+        EvalType::Content(items, _) => items,
+        EvalType::Value(_) => return,
+    };
+    let name = |exp: &EvalType| match exp {
+        EvalType::Value(EvalDataType::String(name)) => Some(name.clone()),
+        EvalType::Content(annotated, _) => match annotated.first() {
+            Some(EvalType::Value(EvalDataType::String(name))) => Some(name.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    match items.first() {
+        Some(EvalType::Value(EvalDataType::String(head))) if head == "begin" || head == "lambda" => {}
+        Some(EvalType::Value(EvalDataType::String(head))) if head == "def" => names.extend(items.get(1).and_then(name)),
+        Some(EvalType::Value(EvalDataType::String(head))) if head == "var" || head == "const" => {
+            names.extend(items.get(1).and_then(|target| VarTarget::parse(target).ok()).map_or(vec![], |target| target.names()));
+            items[2.min(items.len())..].iter().for_each(|item| collect_declarations(item, names));
+        }
+        _ => items.iter().for_each(|item| collect_declarations(item, names)),
+    }
+}
+
+fn declarations(statements: &[EvalType]) -> HashMap<String, Option<EvalType>> {
+    let mut names = vec![];
+    statements.iter().for_each(|statement| collect_declarations(statement, &mut names));
+    names.into_iter().map(|name| (name, None)).collect()
+}
+
+/// Folds pure operations on literals, drops dead `if`/`while` branches and empty blocks, and inlines `const`
+/// bindings with literal values. Every name a scope declares is known up front, so a reference is only
+/// inlined when it cannot resolve to anything but the constant.
+struct Optimizer {
+    scopes: Vec<HashMap<String, Option<EvalType>>>,
+    in_branch: bool,
+}
+
+impl Optimizer {
+    fn lookup(&self, name: &str) -> Option<EvalType> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned().flatten()
+    }
+    fn in_scope<T>(&mut self, scope: HashMap<String, Option<EvalType>>, optimize: impl FnOnce(&mut Optimizer) -> T) -> T {
+        let in_branch = std::mem::replace(&mut self.in_branch, false);
+        self.scopes.push(scope);
+        let result = optimize(self);
+        self.scopes.pop();
+        self.in_branch = in_branch;
+        result
+    }
+    fn in_branch<T>(&mut self, optimize: impl FnOnce(&mut Optimizer) -> T) -> T {
+        let in_branch = std::mem::replace(&mut self.in_branch, true);
+        let result = optimize(self);
+        self.in_branch = in_branch;
+        result
+    }
+    // What `Eva::eval(vec![exp])` runs; `(x)` and `x` are the same tree, so a form that folds to one value becomes it.
+    fn optimize_expression(&mut self, exp: &EvalType) -> EvalType {
+        let items = match exp {
+            EvalType::Content(items, _) => self.optimize_form(items),
+            value => self.optimize_form(std::slice::from_ref(value)),
+        };
+        match items.as_slice() {
+            [single @ EvalType::Value(_)] => single.clone(),
+            _ => EvalType::Content(items, Span::NONE),
+        }
+    }
+    // The rest of `(+ a b)`, `(var x value)` or `(?? value fallback)` is evaluated as one form. A single operand is kept
+    // as its own nested form, so `(var y (inc 2))` is not printed as `(var y inc 2)`.
+    fn optimize_operand(&mut self, rest: &[EvalType]) -> Vec<EvalType> {
+        match rest {
+            [operand] => vec![self.optimize_expression(operand)],
+            _ => self.optimize_form(rest),
+        }
+    }
+    // What `Eva::eval(items)` runs: a list or literal in head position is all that gets evaluated.
+    fn optimize_form(&mut self, items: &[EvalType]) -> Vec<EvalType> {
+        match items.first() {
+            None => vec![],
+            Some(EvalType::Content(inner, _)) => self.optimize_form(inner),
+            Some(EvalType::Value(EvalDataType::String(head))) if !is_quoted(head) => self.optimize_operation(head, &items[1..]),
+            Some(value) => vec![value.clone()],
+        }
+    }
+    fn optimize_operation(&mut self, head: &str, args: &[EvalType]) -> Vec<EvalType> {
+        let form = |args: Vec<EvalType>| [vec![symbol(head)], args].concat();
+        match head {
+            "+" | "*" | "/" | ">" | "<" if !args.is_empty() => {
+                let first = self.optimize_expression(&args[0]);
+                let second = self.optimize_operand(&args[1..]);
+                if let (Some(a), [b]) = (literal(&first), second.as_slice()) {
+                    if let Some(folded) = literal(b).and_then(|b| fold_arithmetic(head, a, b)) {
+                        return vec![folded];
+                    }
+                }
+                form([vec![first], second].concat())
+            }
+            "??" if !args.is_empty() => {
+                let value = self.optimize_expression(&args[0]);
+                // The fallback only runs when the value is null, so what it declares may not exist afterwards.
+                match literal(&value) {
+                    Some(EvalDataType::Null) => self.in_branch(|optimizer| optimizer.optimize_form(&args[1..])),
+                    Some(_) => vec![value],
+                    None => form([vec![value], self.in_branch(|optimizer| optimizer.optimize_operand(&args[1..]))].concat()),
+                }
+            }
+            "var" | "const" | "set" if !args.is_empty() => {
+                let value = self.optimize_operand(&args[1..]);
+                if let (true, false, EvalType::Value(EvalDataType::String(name)), [bound]) = (head == "const", self.in_branch, &args[0], value.as_slice()) {
+                    if literal(bound).is_some() {
+                        if let Some(scope) = self.scopes.last_mut() {
+                            scope.insert(name.clone(), Some(bound.clone()));
+                        }
+                    }
+                }
+                form([vec![args[0].clone()], value].concat())
+            }
+            "if" => match Eva::if_parts(args) {
+                parts @ ([_, _] | [_, _, _]) => match self.optimize_expression(&parts[0]) {
+                    EvalType::Value(EvalDataType::Bool(true)) => self.optimize_form(&parts[1..2]),
+                    EvalType::Value(EvalDataType::Bool(false)) if parts.len() == 2 => vec![EvalType::Value(EvalDataType::Null)],
+                    EvalType::Value(EvalDataType::Bool(false)) => self.optimize_form(&parts[2..3]),
+                    condition => {
+                        let branches: Vec<EvalType> = self.in_branch(|optimizer| parts[1..].iter().map(|branch| optimizer.optimize_expression(branch)).collect());
+                        let parts = [vec![condition], branches].concat();
+                        match args {
+                            [EvalType::Content(..)] => form(vec![EvalType::Content(parts, Span::NONE)]),
+                            _ => form(parts),
+                        }
+                    }
+                },
+                _ => form(args.to_vec()),
+            },
+            "when" | "unless" if !args.is_empty() => {
+                let condition = self.optimize_expression(&args[0]);
+                let body: Vec<EvalType> = self.in_branch(|optimizer| args[1..].iter().map(|statement| optimizer.optimize_expression(statement)).collect());
+                form([vec![condition], body].concat())
+            }
+            "cond" => {
+                let clauses = self.in_branch(|optimizer| args.iter().map(|clause| match clause {
+                    EvalType::Content(items, _) => EvalType::Content(items.iter().map(|item| optimizer.optimize_expression(item)).collect(), Span::NONE),
+                    other => other.clone(),
+                }).collect());
+                form(clauses)
+            }
+            "while" => match args.first() {
+                Some(EvalType::Content(parts, _)) if parts.len() >= 2 => match self.optimize_expression(&parts[0]) {
+                    EvalType::Value(EvalDataType::Bool(false)) => vec![EvalType::Value(EvalDataType::Null)],
+                    condition => {
+                        let body = self.in_branch(|optimizer| optimizer.optimize_expression(&parts[1]));
+                        form(vec![EvalType::Content([vec![condition, body], parts[2..].to_vec()].concat(), Span::NONE)])
+                    }
+                },
+                _ => form(args.to_vec()),
+            },
+            "begin" => self.optimize_begin(args),
+            "match" if !args.is_empty() => {
+                let value = self.optimize_expression(&args[0]);
+                let clauses: Vec<EvalType> = args[1..].iter().map(|clause| self.optimize_clause(clause)).collect();
+                form([vec![value], clauses].concat())
+            }
+            "let" | "let*" | "letrec" => match Eva::let_parts(args) {
+                Some((bindings, body)) => form(self.optimize_let(head, &bindings, body)),
+                None => form(args.to_vec()),
+            },
+            "def" if !args.is_empty() => form([vec![args[0].clone()], self.optimize_function(&args[1..])].concat()),
+            "lambda" => form(self.optimize_function(args)),
+            builtin if string_library::is_string_builtin(builtin) || type_library::is_type_builtin(builtin) => {
+                let args: Vec<EvalType> = args.iter().map(|arg| self.optimize_expression(arg)).collect();
+                let values: Option<Vec<EvalDataType>> = args.iter().map(literal).collect();
+                let folded = values.and_then(|values| match string_library::is_string_builtin(builtin) {
+                    true => to_literal(string_library::call(builtin, &values)),
+                    false => to_literal(type_library::call(builtin, &values)),
+                });
+                match folded {
+                    Some(folded) => vec![folded],
+                    None => form(args),
+                }
+            }
+            name if SPECIAL_FORMS.contains(&name) => form(args.to_vec()),
+            builtin if json_library::is_json_builtin(builtin) || io_library::is_io_builtin(builtin) || fs_library::is_fs_builtin(builtin) || system_library::is_system_builtin(builtin) => form(args.iter().map(|arg| self.optimize_expression(arg)).collect()),
+            // A constant is never a function, so any arguments after it are not evaluated either.
+            name => match self.lookup(name) {
+                Some(constant) => vec![constant],
+                None => form(args.iter().map(|arg| self.optimize_expression(arg)).collect()),
+            },
+        }
+    }
+    fn optimize_begin(&mut self, args: &[EvalType]) -> Vec<EvalType> {
+        let statements = Eva::block_statements(args);
+        let optimized: Vec<EvalType> = self.in_scope(declarations(&statements), |optimizer| statements.iter().map(|statement| optimizer.optimize_expression(statement)).collect());
+        let last = optimized.len().saturating_sub(1);
+        let kept: Vec<EvalType> = optimized.into_iter().enumerate()
+            .filter(|(index, statement)| *index == last || literal(statement).is_none())
+            .map(|(_, statement)| statement)
+            .collect();
+        match kept.as_slice() {
+            [] => vec![EvalType::Value(EvalDataType::Null)],
+            [single] if literal(single).is_some() => vec![single.clone()],
+            // The wrapped shape is only recognised when its first statement is a list.
+            [EvalType::Content(..), ..] => vec![symbol("begin"), EvalType::Content(kept, Span::NONE)],
+            _ => [vec![symbol("begin")], kept].concat(),
+        }
+    }
+    // A `match` clause runs its guard and body in an environment holding the pattern's bindings.
+    fn optimize_clause(&mut self, clause: &EvalType) -> EvalType {
+        let (pattern, guard, body) = match clause_parts(clause) {
+            Some(parts) => parts,
+            None => return clause.clone(),
+        };
+        let names = match Pattern::parse(pattern) {
+            Ok(pattern) => pattern.names(),
+            Err(_) => return clause.clone(),
+        };
+        let mut scope = declarations(&[guard.into_iter().cloned().collect(), body.to_vec()].concat());
+        scope.extend(names.into_iter().map(|name| (name, None)));
+        self.in_scope(scope, |optimizer| {
+            let guard = guard.map_or(vec![], |guard| vec![symbol("when"), optimizer.optimize_expression(guard)]);
+            let body: Vec<EvalType> = body.iter().map(|statement| optimizer.optimize_expression(statement)).collect();
+            EvalType::Content([vec![pattern.clone()], guard, body].concat(), Span::NONE)
+        })
+    }
+    // Only `let` evaluates its values outside the new scope; `let*` and `letrec` values may see the bound names.
+    fn optimize_let(&mut self, kind: &str, bindings: &[LetBinding], body: &[EvalType]) -> Vec<EvalType> {
+        let binding = |name: &str, value: Vec<EvalType>| EvalType::Content([vec![symbol(name)], value].concat(), Span::NONE);
+        let mut scope = declarations(body);
+        scope.extend(bindings.iter().map(|(name, _)| (name.to_string(), None)));
+        if kind == "let" {
+            let bindings: Vec<EvalType> = bindings.iter().map(|(name, value)| binding(name, self.optimize_form(value))).collect();
+            let body: Vec<EvalType> = self.in_scope(scope, |optimizer| body.iter().map(|statement| optimizer.optimize_expression(statement)).collect());
+            return [vec![EvalType::Content(bindings, Span::NONE)], body].concat();
+        }
+        scope.extend(declarations(&bindings.iter().flat_map(|(_, value)| value.to_vec()).collect::<Vec<EvalType>>()));
+        self.in_scope(scope, |optimizer| {
+            let bindings: Vec<EvalType> = bindings.iter().map(|(name, value)| binding(name, optimizer.optimize_form(value))).collect();
+            let body: Vec<EvalType> = body.iter().map(|statement| optimizer.optimize_expression(statement)).collect();
+            [vec![EvalType::Content(bindings, Span::NONE)], body].concat()
+        })
+    }
+    // `params [-> type] body…`; the body runs in its own environment holding the parameters.
+    fn optimize_function(&mut self, items: &[EvalType]) -> Vec<EvalType> {
+        let signature = match Signature::parse(items) {
+            Ok(signature) => signature,
+            Err(_) => return items.to_vec(),
+        };
+        let header = items.len() - signature.body.len();
+        let mut scope = declarations(&signature.body);
+        scope.extend(signature.params.iter().map(|param| (param.name.clone(), None)));
+        let body: Vec<EvalType> = self.in_scope(scope, |optimizer| signature.body.iter().map(|statement| optimizer.optimize_expression(statement)).collect());
+        [items[..header].to_vec(), body].concat()
+    }
+}
+
+/// Optimizes top-level forms that are evaluated one after another in the same environment.
+pub fn optimize_program(program: &[EvalType]) -> Vec<EvalType> {
+    let mut optimizer = Optimizer { scopes: vec![declarations(program)], in_branch: false };
+    program.iter().map(|exp| optimizer.optimize_expression(exp)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::optimizer::optimize_program;
+    use crate::parser::parse;
+    use crate::printer::print_inline;
+    use crate::{get_environment_manager, Eva, EvalResult, EvalType, Span};
+
+    fn optimize(source: &str) -> Vec<String> {
+        optimize_program(&parse(source).unwrap()).iter().map(print_inline).collect()
+    }
+
+    fn run(program: Vec<EvalType>) -> EvalResult {
+        let eva = Eva::new();
+        let env = get_environment_manager();
+        program.into_iter().try_fold(EvalType::Content(vec![], Span::NONE), |_, exp| eva.eval(vec![exp], Rc::clone(&env)))
+    }
+
+    #[test]
+    fn test_fold_literals() {
+        assert_eq!(optimize("(* 2 3) (+ \"a\" \"b\") (< 1 2) (+ (* 2 3) (/ 8 2))"), vec!["6", "\"ab\"", "true", "10"]);
+        assert_eq!(optimize("(/ 1 0) (+ 1 \"a\") (+ x (* 2 3))"), vec!["(/ 1 0)", "(+ 1 \"a\")", "(+ x 6)"]);
+        assert_eq!(optimize("(str-len (upper \"abc\")) (to-number \"x\") (split \"a,b\" \",\")"), vec!["3", "(to-number \"x\")", "(split \"a,b\" \",\")"]);
+    }
+
+    #[test]
+    fn test_dead_branches() {
+        assert_eq!(optimize("(if ((< 1 2) (+ x 1) (set x 0)))"), vec!["(+ x 1)"]);
+        assert_eq!(optimize("(if (false 1 (begin (var y 2) y)))"), vec!["(begin ((var y 2) y))"]);
+        assert_eq!(optimize("(while ((> 1 2) (set x 1)))"), vec!["null"]);
+        assert_eq!(optimize("(?? null (* 2 3)) (?? 1 x) (?? x 2)"), vec!["6", "1", "(?? x 2)"]);
+        assert_eq!(optimize("(if ((> x 1) (* 2 2) 0))"), vec!["(if ((> x 1) 4 0))"]);
+    }
+
+    #[test]
+    fn test_empty_blocks() {
+        assert_eq!(optimize("(begin) (begin ((begin) (var x 1) 2 x))"), vec!["null", "(begin ((var x 1) x))"]);
+        assert_eq!(optimize("(begin 1 (* 2 3))"), vec!["6"]);
+    }
+
+    #[test]
+    fn test_inline_constants() {
+        assert_eq!(optimize("(const n 4) (var total (* n 2))"), vec!["(const n 4)", "(var total 8)"]);
+        assert_eq!(optimize("(const n 4) (begin (var n 1) (+ n 1))"), vec!["(const n 4)", "(begin ((var n 1) (+ n 1)))"]);
+        assert_eq!(optimize("(const n 4) (def f (n) (+ n 1)) (def g () (+ n 1))"), vec!["(const n 4)", "(def f (n) (+ n 1))", "(def g () 5)"]);
+        assert_eq!(optimize("(if ((> x 0) (const n 1) 0)) n"), vec!["(if ((> x 0) (const n 1) 0))", "n"]);
+        assert_eq!(optimize("(const n 4) (let ((n (* 2 3)) (m n)) (+ n m))"), vec!["(const n 4)", "(let ((n 6) (m 4)) (+ n m))"]);
+    }
+
+    #[test]
+    fn test_semantics_preserved() {
+        let programs = [
+            "(begin ((var counter 0) (var result 0) (while ((< counter 10) (begin ((set result (+ result (* 2 3))) (set counter (+ counter 1)))))) result))",
+            "(const step 2) (var total 0) (def add-step (x) (+ x step)) (set total (add-step (add-step total))) total",
+            "(var x 5) (if ((> x (* 2 2)) (format \"{} is big\" x) (begin)))",
+            "(begin (const base 10) (begin ((var base 1) (+ base (str-len \"abc\")))))",
+            "(const n 4) (let ((n 1) (m n)) (+ n m))",
+            "(const n 4) (let* ((m n) (n (* m 2))) (+ n m))",
+            "(const n 4) (begin (var (n k) (split \"a,b\" \",\")) (+ n k)) (match 1 (0 n) (n when (< n 2) (+ n n)) (_ n))",
+            "(def pick ((flag : bool)) -> number (if (flag (+ 1 1) (* 3 3)))) (+ (pick true) (pick false))",
+        ];
+        for source in programs {
+            let program = parse(source).unwrap();
+            assert_eq!(run(optimize_program(&program)), run(program), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_operands_stay_nested() {
+        let source = "(def inc (x) (+ x 1)) (var y (inc 2)) (var z (+ y (inc (* 1 3)))) (?? y (inc 4))";
+        assert_eq!(optimize(source), vec!["(def inc (x) (+ x 1))", "(var y (inc 2))", "(var z (+ y (inc 3)))", "(?? y (inc 4))"]);
+        let program = parse(source).unwrap();
+        assert_eq!(run(optimize_program(&program)), run(program));
+    }
+
+    #[test]
+    fn test_default_fallback_is_a_branch() {
+        let source = "(var y 2) (?? y (const n 1)) n";
+        assert_eq!(optimize(source), vec!["(var y 2)", "(?? y (const n 1))", "n"]);
+        let program = parse(source).unwrap();
+        let unoptimized = run(program.clone());
+        assert!(unoptimized.is_err(), "{:?}", unoptimized);
+        assert_eq!(run(optimize_program(&program)), unoptimized);
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+use crate::{EvalDataType, EvalType, Span};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Source as written, including comments and blank lines, which `EvalType` has no room for.
+#[derive(PartialEq, Debug, Clone)]
+pub enum SyntaxNode {
+    Atom { text: String, line: usize, column: usize },
+    List { items: Vec<SyntaxNode>, line: usize, column: usize },
+    Comment { text: String, trailing: bool },
+    BlankLine,
+}
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(source: &'a str) -> Reader<'a> {
+        Reader { chars: source.chars().peekable(), line: 1, column: 1 }
+    }
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+    fn error(&self, message: String) -> ParseError {
+        ParseError { message, line: self.line, column: self.column }
+    }
+    fn skip_whitespace(&mut self) -> usize {
+        let mut newlines = 0;
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            if c == '\n' {
+                newlines += 1;
+            }
+            self.next();
+        }
+        newlines
+    }
+    // Reads items up to `)` (or the end of input at the top level), keeping comments and blank lines.
+    fn read_sequence(&mut self, top_level: bool) -> Result<Vec<SyntaxNode>, ParseError> {
+        let mut items = vec![];
+        loop {
+            let newlines = self.skip_whitespace();
+            let has_items = items.iter().any(|item| !matches!(item, SyntaxNode::BlankLine));
+            if newlines >= 2 && has_items && items.last() != Some(&SyntaxNode::BlankLine) {
+                items.push(SyntaxNode::BlankLine);
+            }
+            match self.peek() {
+                None if top_level => break,
+                None => return Err(self.error("unclosed `(`".to_owned())),
+                Some(')') if top_level => return Err(self.error("unexpected `)`".to_owned())),
+                Some(')') => {
+                    self.next();
+                    break;
+                }
+                Some(';') => {
+                    let mut text = String::new();
+                    while let Some(c) = self.peek().filter(|c| *c != '\n') {
+                        text.push(c);
+                        self.next();
+                    }
+                    items.push(SyntaxNode::Comment { text: text.trim_end().to_owned(), trailing: newlines == 0 && has_items });
+                }
+                Some(_) => items.push(self.read_node()?),
+            }
+        }
+        if items.last() == Some(&SyntaxNode::BlankLine) {
+            items.pop();
+        }
+        Ok(items)
+    }
+    fn read_node(&mut self) -> Result<SyntaxNode, ParseError> {
+        let (line, column) = (self.line, self.column);
+        match self.peek() {
+            Some('(') => {
+                self.next();
+                Ok(SyntaxNode::List { items: self.read_sequence(false)?, line, column })
+            }
+            Some('"') => Ok(SyntaxNode::Atom { text: self.read_string_token()?, line, column }),
+            _ => {
+                let mut text = String::new();
+                while let Some(c) = self.peek().filter(|c| !c.is_whitespace() && !"()\";".contains(*c)) {
+                    text.push(c);
+                    self.next();
+                }
+                Ok(SyntaxNode::Atom { text, line, column })
+            }
+        }
+    }
+    fn read_string_token(&mut self) -> Result<String, ParseError> {
+        let mut token = String::new();
+        token.extend(self.next());
+        loop {
+            match self.next() {
+                None => return Err(self.error("unterminated string literal".to_owned())),
+                Some('"') => {
+                    token.push('"');
+                    return Ok(token);
+                }
+                Some('\\') => {
+                    token.push('\\');
+                    match self.next() {
+                        Some(c) => token.push(c),
+                        None => return Err(self.error("unterminated string literal".to_owned())),
+                    }
+                }
+                Some(c) => token.push(c),
+            }
+        }
+    }
+}
+
+pub fn parse_syntax(source: &str) -> Result<Vec<SyntaxNode>, ParseError> {
+    Reader::new(source).read_sequence(true)
+}
+
+fn unescape(token: &str, line: usize, column: usize) -> Result<String, ParseError> {
+    let mut text = String::new();
+    let mut chars = token[1..token.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some('r') => text.push('\r'),
+            Some('"') => text.push('"'),
+            Some('\\') => text.push('\\'),
+            other => return Err(ParseError { message: format!("unknown escape `\\{}`", other.unwrap_or(' ')), line, column }),
+        }
+    }
+    Ok(text)
+}
+
+pub fn lower(node: &SyntaxNode) -> Result<Option<EvalType>, ParseError> {
+    match node {
+        SyntaxNode::Comment { .. } | SyntaxNode::BlankLine => Ok(None),
+        SyntaxNode::List { items, line, column } => Ok(Some(EvalType::Content(lower_all(items)?, Span::new(*line, *column)))),
+        // String literals are kept in the `'text'` form that `Eva::process_value_string` recognises.
+        SyntaxNode::Atom { text, line, column } if text.starts_with('"') => {
+            Ok(Some(EvalType::Value(EvalDataType::String(format!("'{}'", unescape(text, *line, *column)?)))))
+        }
+        SyntaxNode::Atom { text, line, column } if text.chars().all(|c| c.is_ascii_digit()) => text.parse::<u128>()
+            .map(|number| Some(EvalType::Value(EvalDataType::Number(number))))
+            .map_err(|_| ParseError { message: format!("number `{}` is too large", text), line: *line, column: *column }),
+        SyntaxNode::Atom { text, .. } => Ok(Some(EvalType::Value(match text.as_str() {
+            "true" => EvalDataType::Bool(true),
+            "false" => EvalDataType::Bool(false),
+            "null" => EvalDataType::Null,
+            _ => EvalDataType::String(text.clone()),
+        }))),
+    }
+}
+
+fn lower_all(nodes: &[SyntaxNode]) -> Result<Vec<EvalType>, ParseError> {
+    let mut program = vec![];
+    for node in nodes {
+        program.extend(lower(node)?);
+    }
+    Ok(program)
+}
+
+pub fn parse(source: &str) -> Result<Vec<EvalType>, ParseError> {
+    lower_all(&parse_syntax(source)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{parse, parse_syntax, SyntaxNode};
+    use crate::{EvalDataType, EvalType, Span};
+
+    #[test]
+    fn test_parse_atoms() {
+        assert_eq!(parse("42 true false null x \"a \\\"b\\\"\"").unwrap(), vec![
+            EvalType::Value(EvalDataType::Number(42)),
+            EvalType::Value(EvalDataType::Bool(true)),
+            EvalType::Value(EvalDataType::Bool(false)),
+            EvalType::Value(EvalDataType::Null),
+            EvalType::Value(EvalDataType::String("x".to_owned())),
+            EvalType::Value(EvalDataType::String("'a \"b\"'".to_owned())),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_forms_and_comments() {
+        assert_eq!(parse("; leading comment\n(var x (+ 1 2)) ; trailing\n").unwrap(), vec![
+            EvalType::Content(vec![
+                EvalType::Value(EvalDataType::String("var".to_owned())),
+                EvalType::Value(EvalDataType::String("x".to_owned())),
+                EvalType::Content(vec![
+                    EvalType::Value(EvalDataType::String("+".to_owned())),
+                    EvalType::Value(EvalDataType::Number(1)),
+                    EvalType::Value(EvalDataType::Number(2)),
+                ], Span::NONE),
+            ], Span::NONE),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_syntax_keeps_comments() {
+        let nodes = parse_syntax("; header\n(var x 1) ; one\n\n\n(var y 2)").unwrap();
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(nodes[0], SyntaxNode::Comment { text: "; header".to_owned(), trailing: false });
+        assert_eq!(nodes[2], SyntaxNode::Comment { text: "; one".to_owned(), trailing: true });
+        assert_eq!(nodes[3], SyntaxNode::BlankLine);
+        assert!(matches!(nodes[4], SyntaxNode::List { line: 5, column: 1, .. }));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = parse("(begin\n  (var x 1)").unwrap_err();
+        assert_eq!((error.line, error.message.as_str()), (2, "unclosed `(`"));
+        assert_eq!(parse(")").unwrap_err().message, "unexpected `)`");
+        assert_eq!(parse("\"open").unwrap_err().message, "unterminated string literal");
+        assert_eq!(parse("\"bad \\q\"").unwrap_err().message, "unknown escape `\\q`");
+    }
+}
@@ -0,0 +1,201 @@
+use crate::printer::print_inline;
+use crate::types::{parse_type, Type};
+use crate::{EvalDataType, EvalType};
+
+/// A pattern of `match` or of a destructuring `var`:
+/// - `_` matches anything;
+/// - a number, bool, `null` or string literal matches an equal value;
+/// - any other symbol matches anything and binds it;
+/// - `(p1 p2 . rest)` matches a list, with `rest` taking the items after the fixed ones;
+/// - `(map "key" p …)` matches a map that has every listed key.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Literal(EvalDataType),
+    Bind(String),
+    List { items: Vec<Pattern>, rest: Option<Box<Pattern>> },
+    Map(Vec<(String, Pattern)>),
+}
+
+fn quoted(text: &str) -> Option<&str> {
+    match text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        true => Some(&text[1..text.len() - 1]),
+        false => None,
+    }
+}
+
+impl Pattern {
+    pub fn parse(exp: &EvalType) -> Result<Pattern, String> {
+        let pattern = Pattern::parse_node(exp)?;
+        let mut names = pattern.names();
+        names.sort();
+        match names.windows(2).find(|pair| pair[0] == pair[1]) {
+            Some(pair) => Err(format!("`{}` is bound twice in pattern {}", pair[0], print_inline(exp))),
+            None => Ok(pattern),
+        }
+    }
+    fn parse_node(exp: &EvalType) -> Result<Pattern, String> {
+        match exp {
+            EvalType::Value(EvalDataType::String(text)) => Ok(match (text.as_str(), quoted(text)) {
+                ("_", _) => Pattern::Wildcard,
+                (_, Some(literal)) => Pattern::Literal(EvalDataType::String(literal.to_owned())),
+                (name, None) => Pattern::Bind(name.to_owned()),
+            }),
+            EvalType::Value(value @ (EvalDataType::Number(_) | EvalDataType::Bool(_) | EvalDataType::Null)) => Ok(Pattern::Literal(value.clone())),
+            EvalType::Content(items, _) if items.first() == Some(&EvalType::Value(EvalDataType::String("map".to_owned()))) => {
+                if items.len() % 2 == 0 {
+                    return Err(format!("map pattern {} expects key and pattern pairs", print_inline(exp)));
+                }
+                items[1..].chunks(2).map(|pair| match &pair[0] {
+                    EvalType::Value(EvalDataType::String(key)) if quoted(key).is_some() => Ok((quoted(key).unwrap_or_default().to_owned(), Pattern::parse_node(&pair[1])?)),
+                    key => Err(format!("map pattern keys must be strings, found {}", print_inline(key))),
+                }).collect::<Result<Vec<(String, Pattern)>, String>>().map(Pattern::Map)
+            }
+            EvalType::Content(items, _) => {
+                let dot = EvalType::Value(EvalDataType::String(".".to_owned()));
+                let (items, rest) = match items.iter().position(|item| *item == dot) {
+                    Some(index) if index + 2 == items.len() => (&items[..index], Some(Box::new(Pattern::parse_node(&items[index + 1])?))),
+                    Some(_) => return Err(format!("`.` must come right before the last pattern in {}", print_inline(exp))),
+                    None => (&items[..], None),
+                };
+                Ok(Pattern::List { items: items.iter().map(Pattern::parse_node).collect::<Result<Vec<Pattern>, String>>()?, rest })
+            }
+            other => Err(format!("invalid pattern {}", print_inline(other))),
+        }
+    }
+    /// The names the pattern binds, in the order they appear.
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            Pattern::Wildcard | Pattern::Literal(_) => vec![],
+            Pattern::Bind(name) => vec![name.clone()],
+            Pattern::List { items, rest } => items.iter().chain(rest.as_deref()).flat_map(Pattern::names).collect(),
+            Pattern::Map(entries) => entries.iter().flat_map(|(_, pattern)| pattern.names()).collect(),
+        }
+    }
+    /// The bindings that make `value` match, or `None` when it does not.
+    pub fn bind(&self, value: &EvalDataType) -> Option<Vec<(String, EvalDataType)>> {
+        match (self, value) {
+            (Pattern::Wildcard, _) => Some(vec![]),
+            (Pattern::Literal(literal), value) => (literal == value).then(Vec::new),
+            (Pattern::Bind(name), value) => Some(vec![(name.clone(), value.clone())]),
+            (Pattern::List { items: patterns, rest }, EvalDataType::List(values)) => {
+                let fits = match rest {
+                    Some(_) => values.len() >= patterns.len(),
+                    None => values.len() == patterns.len(),
+                };
+                if !fits {
+                    return None;
+                }
+                let mut bindings = vec![];
+                for (pattern, value) in patterns.iter().zip(values) {
+                    bindings.extend(pattern.bind(value)?);
+                }
+                if let Some(rest) = rest {
+                    bindings.extend(rest.bind(&EvalDataType::List(values[patterns.len()..].to_vec()))?);
+                }
+                Some(bindings)
+            }
+            (Pattern::Map(entries), EvalDataType::Map(values)) => {
+                let mut bindings = vec![];
+                for (key, pattern) in entries {
+                    bindings.extend(pattern.bind(values.get(key)?)?);
+                }
+                Some(bindings)
+            }
+            _ => None,
+        }
+    }
+    /// Whether the pattern matches every value, so later `match` clauses are never reached.
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, Pattern::Wildcard | Pattern::Bind(_))
+    }
+}
+
+/// What a `var` declares: a name, an annotated `(name : type)` or, for any other list, a destructuring pattern.
+/// The `:` keeps annotations apart from patterns, so `(a list)` destructures a pair.
+#[derive(PartialEq, Debug, Clone)]
+pub enum VarTarget {
+    Name(String, Option<Type>),
+    Pattern(Pattern),
+}
+
+impl VarTarget {
+    pub fn parse(exp: &EvalType) -> Result<VarTarget, String> {
+        match exp {
+            EvalType::Value(EvalDataType::String(name)) if quoted(name).is_none() => Ok(VarTarget::Name(name.clone(), None)),
+            EvalType::Content(items, _) => match items.as_slice() {
+                [EvalType::Value(EvalDataType::String(name)), EvalType::Value(EvalDataType::String(colon)), annotation] if colon == ":" && quoted(name).is_none() => {
+                    Ok(VarTarget::Name(name.clone(), Some(parse_type(annotation)?)))
+                }
+                items if items.contains(&EvalType::Value(EvalDataType::String(":".to_owned()))) => {
+                    Err(format!("`:` must annotate a single name, as in (x : number), not {}", print_inline(exp)))
+                }
+                _ => Ok(VarTarget::Pattern(Pattern::parse(exp)?)),
+            },
+            other => Err(format!("invalid variable name {}", print_inline(other))),
+        }
+    }
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            VarTarget::Name(name, _) => vec![name.clone()],
+            VarTarget::Pattern(pattern) => pattern.names(),
+        }
+    }
+}
+
+/// `(pattern [when guard] body…)`, the clause of a `match`.
+pub fn clause_parts(clause: &EvalType) -> Option<(&EvalType, Option<&EvalType>, &[EvalType])> {
+    match clause {
+        EvalType::Content(items, _) => match items.as_slice() {
+            [pattern, EvalType::Value(EvalDataType::String(when)), guard, body @ ..] if when == "when" => Some((pattern, Some(guard), body)),
+            [pattern, body @ ..] => Some((pattern, None, body)),
+            [] => None,
+        },
+        EvalType::Value(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse;
+    use crate::pattern::{Pattern, VarTarget};
+    use crate::types::Type;
+    use crate::EvalDataType;
+
+    fn pattern(source: &str) -> Result<Pattern, String> {
+        Pattern::parse(&parse(source).unwrap()[0])
+    }
+
+    #[test]
+    fn test_bind_lists_and_maps() {
+        let list = EvalDataType::from(vec![1u64, 2, 3]);
+        assert_eq!(pattern("(a _ . rest)").unwrap().bind(&list), Some(vec![
+            ("a".to_owned(), EvalDataType::Number(1)),
+            ("rest".to_owned(), EvalDataType::from(vec![3u64])),
+        ]));
+        assert_eq!(pattern("(a b)").unwrap().bind(&list), None);
+        assert_eq!(pattern("(1 . _)").unwrap().bind(&list), Some(vec![]));
+        let user = EvalDataType::Map([("name".to_owned(), EvalDataType::from("eva")), ("admin".to_owned(), EvalDataType::Bool(true))].into());
+        assert_eq!(pattern("(map \"name\" n \"admin\" true)").unwrap().bind(&user), Some(vec![("n".to_owned(), EvalDataType::from("eva"))]));
+        assert_eq!(pattern("(map \"email\" e)").unwrap().bind(&user), None);
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert_eq!(pattern("(a a)"), Err("`a` is bound twice in pattern (a a)".to_owned()));
+        assert_eq!(pattern("(a . b c)"), Err("`.` must come right before the last pattern in (a . b c)".to_owned()));
+        assert_eq!(pattern("(map name n)"), Err("map pattern keys must be strings, found name".to_owned()));
+    }
+
+    #[test]
+    fn test_var_targets() {
+        let targets: Vec<VarTarget> = parse("x (x : number) (x y) (head . tail) (a list)").unwrap().iter().map(|exp| VarTarget::parse(exp).unwrap()).collect();
+        assert_eq!(targets[0], VarTarget::Name("x".to_owned(), None));
+        assert_eq!(targets[1], VarTarget::Name("x".to_owned(), Some(Type::Number)));
+        assert_eq!(targets[2].names(), vec!["x", "y"]);
+        assert_eq!(targets[3].names(), vec!["head", "tail"]);
+        assert_eq!(targets[4].names(), vec!["a", "list"]);
+        let invalid: Vec<String> = parse("(x : float) (a b : number)").unwrap().iter().map(|exp| VarTarget::parse(exp).unwrap_err()).collect();
+        assert_eq!(invalid, vec!["unknown type `float`", "`:` must annotate a single name, as in (x : number), not (a b : number)"]);
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt;
+use crate::parser::SyntaxNode;
+use crate::{EvalDataType, EvalType};
+
+pub const MAX_WIDTH: usize = 80;
+const INDENT: usize = 2;
+
+impl fmt::Display for EvalDataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalDataType::String(text) => write!(f, "{}", quote(text)),
+            EvalDataType::Number(number) => write!(f, "{}", number),
+            EvalDataType::Bool(value) => write!(f, "{}", value),
+            EvalDataType::List(items) => write!(f, "({})", items.iter().map(|item| item.to_string()).collect::<Vec<String>>().join(" ")),
+            EvalDataType::Error(error) => write!(f, "#<error {}>", error),
+            EvalDataType::Function(function) => write!(f, "#<function {}>", function.name),
+            EvalDataType::Map(entries) => {
+                let entries: Vec<String> = entries.iter().map(|(key, value)| format!("{} {}", quote(key), value)).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            EvalDataType::Null => write!(f, "null"),
+        }
+    }
+}
+
+impl fmt::Display for EvalType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalType::Value(value) => write!(f, "{}", value),
+            EvalType::Content(content, _) => write!(f, "({})", content.iter().map(|item| item.to_string()).collect::<Vec<String>>().join(" ")),
+        }
+    }
+}
+
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// In a program tree a `String` is either a symbol or a `'text'` literal, see `Eva::process_value_string`.
+fn print_atom(value: &EvalDataType) -> String {
+    match value {
+        EvalDataType::String(text) if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') => quote(&text[1..text.len() - 1]),
+        EvalDataType::String(symbol) => symbol.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub fn to_syntax(exp: &EvalType) -> SyntaxNode {
+    match exp {
+        EvalType::Value(value) => SyntaxNode::Atom { text: print_atom(value), line: 0, column: 0 },
+        EvalType::Content(content, _) => SyntaxNode::List { items: content.iter().map(to_syntax).collect(), line: 0, column: 0 },
+    }
+}
+
+/// How many arguments of a special form stay on the line of its keyword when the form is broken up.
+pub fn header_arguments(head: &str) -> Option<usize> {
+    match head {
+        "begin" | "cond" => Some(0),
+        "if" | "when" | "unless" | "while" | "var" | "const" | "set" | "lambda" | "let" | "let*" | "letrec" | "match" => Some(1),
+        "def" | "class" => Some(2),
+        _ => None,
+    }
+}
+
+fn head_symbol(items: &[SyntaxNode]) -> Option<&str> {
+    match items.first() {
+        Some(SyntaxNode::Atom { text, .. }) => Some(text),
+        _ => None,
+    }
+}
+
+// Block forms get one statement per line as soon as they hold more than a single body expression.
+fn must_break(items: &[SyntaxNode]) -> bool {
+    let body = match head_symbol(items).and_then(header_arguments) {
+        Some(header) if matches!(head_symbol(items), Some("begin" | "def" | "class")) => items.len().saturating_sub(1 + header),
+        _ => return false,
+    };
+    body > 1 || (head_symbol(items) == Some("class") && body > 0)
+}
+
+fn print_flat(node: &SyntaxNode) -> Option<String> {
+    match node {
+        SyntaxNode::Atom { text, .. } => Some(text.clone()),
+        SyntaxNode::List { items, .. } => {
+            let items = items.iter().map(print_flat).collect::<Option<Vec<String>>>()?;
+            Some(format!("({})", items.join(" ")))
+        }
+        SyntaxNode::Comment { .. } | SyntaxNode::BlankLine => None,
+    }
+}
+
+fn current_column(out: &str) -> usize {
+    out[out.rfind('\n').map_or(0, |index| index + 1)..].chars().count()
+}
+
+pub fn layout(node: &SyntaxNode, indent: usize, out: &mut String) {
+    let items = match node {
+        SyntaxNode::List { items, .. } => items,
+        SyntaxNode::Comment { text, .. } => return out.push_str(text),
+        SyntaxNode::BlankLine => return,
+        SyntaxNode::Atom { text, .. } => return out.push_str(text),
+    };
+    if let Some(flat) = print_flat(node) {
+        if current_column(out) + flat.chars().count() <= MAX_WIDTH && !must_break(items) {
+            return out.push_str(&flat);
+        }
+    }
+    let header = 1 + head_symbol(items).map_or(0, |head| header_arguments(head).unwrap_or(1));
+    // `(def name (params) -> type …)` keeps its return annotation next to the parameters.
+    let header = match items.get(header) {
+        Some(SyntaxNode::Atom { text, .. }) if text == "->" && matches!(head_symbol(items), Some("def" | "lambda")) => header + 2,
+        _ => header,
+    };
+    let header = items.iter().take(header).take_while(|item| matches!(item, SyntaxNode::Atom { .. } | SyntaxNode::List { .. })).count();
+    out.push('(');
+    for (index, item) in items[..header].iter().enumerate() {
+        if index > 0 {
+            out.push(' ');
+        }
+        let column = current_column(out);
+        layout(item, column, out);
+    }
+    for item in &items[header..] {
+        match item {
+            SyntaxNode::Comment { text, trailing: true } => {
+                out.push(' ');
+                out.push_str(text);
+            }
+            SyntaxNode::BlankLine => out.push('\n'),
+            item => {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent + INDENT));
+                layout(item, indent + INDENT, out);
+            }
+        }
+    }
+    if matches!(items.last(), Some(SyntaxNode::Comment { .. })) {
+        out.push('\n');
+        out.push_str(&" ".repeat(indent));
+    }
+    out.push(')');
+}
+
+pub fn print_nodes(nodes: &[SyntaxNode]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        match node {
+            SyntaxNode::Comment { text, trailing: true } => {
+                out.pop();
+                out.push(' ');
+                out.push_str(text);
+            }
+            SyntaxNode::BlankLine => {}
+            node => layout(node, 0, &mut out),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn print_expression(exp: &EvalType, indent: usize, out: &mut String) {
+    layout(&to_syntax(exp), indent, out);
+}
+
+/// Source text of `exp` on a single line, for quoting an expression inside a message.
+pub fn print_inline(exp: &EvalType) -> String {
+    print_flat(&to_syntax(exp)).unwrap_or_default()
+}
+
+pub fn print_program(program: &[EvalType]) -> String {
+    print_nodes(&program.iter().map(to_syntax).collect::<Vec<SyntaxNode>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval_error::{ErrorKind, EvalError};
+    use crate::parser::parse;
+    use crate::printer::print_program;
+    use crate::{EvalDataType, EvalType};
+
+    #[test]
+    fn test_display_values() {
+        assert_eq!(EvalDataType::Number(10).to_string(), "10");
+        assert_eq!(EvalDataType::String("say \"hi\"".to_owned()).to_string(), "\"say \\\"hi\\\"\"");
+        assert_eq!(EvalDataType::Bool(true).to_string(), "true");
+        assert_eq!(EvalDataType::List(vec![EvalDataType::Number(1), EvalDataType::Number(2), EvalDataType::Number(3)]).to_string(), "(1 2 3)");
+        assert_eq!(EvalDataType::Error(EvalError::new(ErrorKind::NotFound, "missing".to_owned())).to_string(), "#<error not-found: missing>");
+        assert_eq!(EvalType::Value(EvalDataType::Number(10)).to_string(), "10");
+    }
+
+    #[test]
+    fn test_print_layout() {
+        let program = parse("(begin (var x 10) (while ((< x 20) (set x (+ x 1)))) (if (> x 10) \"big\" \"small\"))").unwrap();
+        assert_eq!(print_program(&program), concat!(
+            "(begin\n",
+            "  (var x 10)\n",
+            "  (while ((< x 20) (set x (+ x 1))))\n",
+            "  (if (> x 10) \"big\" \"small\"))\n",
+        ));
+    }
+
+    #[test]
+    fn test_print_breaks_long_forms() {
+        let program = parse("(var message (format \"{} has {} items in the current shopping basket\" customer-name item-count))").unwrap();
+        assert_eq!(print_program(&program), concat!(
+            "(var message\n",
+            "  (format \"{} has {} items in the current shopping basket\"\n",
+            "    customer-name\n",
+            "    item-count))\n",
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = concat!(
+            "(begin ((var counter 0) (var result 0) (while ((< counter 10) (begin ((set result (+ result 1))",
+            " (set counter (+ counter 1)))))) (var label \"multi\\nline \\\"quoted\\\"\") result) x)\n",
+            "(if ((> x 10) (set y 20) (set y 30)))\n",
+            "(format \"{} and {}\" true false 340282366920938463463374607431768211455)\n",
+        );
+        let tree = parse(source).unwrap();
+        assert_eq!(parse(&print_program(&tree)).unwrap(), tree);
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::{self, BufRead, Write};
+use crate::capabilities::{Capabilities, ALLOW_FLAGS};
+use crate::parser::parse;
+use crate::Interpreter;
+
+const HELP: &str = ":save <file>  write the session to an image\n:load <file>  replace the session with an image\n:quit         leave the repl";
+
+/// What the REPL does with one line of input.
+#[derive(PartialEq, Debug)]
+pub enum Reply {
+    /// The value of the evaluated forms, or the message of a command.
+    Output(String),
+    Error(String),
+    /// The input so far has an unclosed `(`, so more lines are needed.
+    More,
+    Quit,
+}
+
+/// A session of the interactive interpreter; lines are fed in one at a time.
+#[derive(Default)]
+pub struct Repl {
+    interpreter: Interpreter,
+    pending: String,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl::default()
+    }
+    pub fn with_capabilities(capabilities: Capabilities) -> Repl {
+        Repl { interpreter: Interpreter::new().with_capabilities(capabilities), pending: String::new() }
+    }
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+    pub fn is_continuing(&self) -> bool {
+        !self.pending.is_empty()
+    }
+    pub fn feed(&mut self, line: &str) -> Reply {
+        if self.pending.is_empty() && line.trim_start().starts_with(':') {
+            return self.command(line.trim());
+        }
+        self.pending.push_str(line);
+        self.pending.push('\n');
+        if let Err(error) = parse(&self.pending) {
+            if error.message == "unclosed `(`" {
+                return Reply::More;
+            }
+        }
+        let source = std::mem::take(&mut self.pending);
+        match self.interpreter.eval_str(&source) {
+            Ok(value) => Reply::Output(value.to_string()),
+            Err(error) => Reply::Error(error.to_string()),
+        }
+    }
+    fn command(&mut self, line: &str) -> Reply {
+        let (command, argument) = line.split_once(char::is_whitespace).map_or((line, ""), |(command, argument)| (command, argument.trim()));
+        match (command, argument) {
+            (":quit" | ":q", _) => Reply::Quit,
+            (":help", _) => Reply::Output(HELP.to_owned()),
+            (":save" | ":load", "") => Reply::Error(format!("usage: {} <file>", command)),
+            (":save", path) => match self.interpreter.save_image(path) {
+                Ok(()) => Reply::Output(format!("saved {}", path)),
+                Err(error) => Reply::Error(error.to_string()),
+            },
+            (":load", path) => match self.interpreter.load_image(path) {
+                Ok(()) => Reply::Output(format!("loaded {}", path)),
+                Err(error) => Reply::Error(error.to_string()),
+            },
+            _ => Reply::Error(format!("unknown command `{}`; try :help", command)),
+        }
+    }
+}
+
+/// `eva repl [--allow-…] [image]`: reads forms from standard input and prints their values, starting from `image`
+/// if given.
+pub fn run(args: &[String]) -> i32 {
+    let capabilities = match Capabilities::from_flags(args) {
+        Ok(capabilities) => capabilities,
+        Err(error) => {
+            eprintln!("{}", error);
+            return 2;
+        }
+    };
+    let mut repl = Repl::with_capabilities(capabilities);
+    match args.iter().filter(|arg| !arg.starts_with("--")).collect::<Vec<&String>>().as_slice() {
+        [] => {}
+        [image] => {
+            if let Err(error) = repl.interpreter().load_image(image) {
+                eprintln!("{}", error);
+                return 2;
+            }
+        }
+        _ => {
+            eprintln!("usage: eva repl {} [image]", ALLOW_FLAGS);
+            return 2;
+        }
+    }
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("{}", if repl.is_continuing() { "... " } else { "> " });
+        let _ = io::stdout().flush();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(error)) => {
+                eprintln!("{}", error);
+                return 1;
+            }
+            None => return 0,
+        };
+        match repl.feed(&line) {
+            Reply::Output(output) => println!("{}", output),
+            Reply::Error(error) => eprintln!("error: {}", error),
+            Reply::More => {}
+            Reply::Quit => return 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repl::{Repl, Reply};
+
+    #[test]
+    fn test_multi_line_input() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.feed("(def double (x)"), Reply::More);
+        assert!(repl.is_continuing());
+        assert!(matches!(repl.feed("  (+ x x))"), Reply::Output(_)));
+        assert_eq!(repl.feed("(double 21)"), Reply::Output("42".to_owned()));
+        assert_eq!(repl.feed("(set missing 1)"), Reply::Error("assignment to undeclared variable `missing`".to_owned()));
+        assert_eq!(repl.feed(":frobnicate"), Reply::Error("unknown command `:frobnicate`; try :help".to_owned()));
+        assert_eq!(repl.feed(":quit"), Reply::Quit);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("eva-repl-{}.image", std::process::id()));
+        let path = path.display().to_string();
+        let mut repl = Repl::new();
+        repl.feed("(var total 40) (def add (x) (set total (+ total x)))");
+        assert_eq!(repl.feed(&format!(":save {}", path)), Reply::Output(format!("saved {}", path)));
+        let mut fresh = Repl::new();
+        assert_eq!(fresh.feed(&format!(":load {}", path)), Reply::Output(format!("loaded {}", path)));
+        assert_eq!(fresh.feed("(add 2)"), Reply::Output("42".to_owned()));
+        assert_eq!(fresh.feed(":load"), Reply::Error("usage: :load <file>".to_owned()));
+        std::fs::remove_file(path).unwrap();
+    }
+}
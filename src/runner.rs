@@ -0,0 +1,72 @@
+use std::fs;
+use crate::capabilities::{Capabilities, ALLOW_FLAGS};
+use crate::optimizer::optimize_program;
+use crate::parser::parse;
+use crate::printer::print_program;
+use crate::Interpreter;
+
+/// `eva run [--dump-optimized] [--no-optimize] [--allow-…] file`: evaluates the top-level forms in order and prints
+/// the last value. Scripts get `Capabilities::standard()` plus whatever the `--allow-*` flags grant.
+pub fn run(args: &[String]) -> i32 {
+    let dump = args.iter().any(|arg| arg == "--dump-optimized");
+    let optimize = !args.iter().any(|arg| arg == "--no-optimize");
+    let files: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let file = match files.as_slice() {
+        [file] => file,
+        _ => {
+            eprintln!("usage: eva run [--dump-optimized] [--no-optimize] {} <file>", ALLOW_FLAGS);
+            return 2;
+        }
+    };
+    let capabilities = match Capabilities::from_flags(args) {
+        Ok(capabilities) => capabilities,
+        Err(error) => {
+            eprintln!("{}", error);
+            return 2;
+        }
+    };
+    let program = match fs::read_to_string(file).map_err(|error| error.to_string())
+        .and_then(|source| parse(&source).map_err(|error| error.to_string())) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}: {}", file, error);
+            return 2;
+        }
+    };
+    let program = if optimize { optimize_program(&program) } else { program };
+    if dump {
+        print!("{}", print_program(&program));
+        return 0;
+    }
+    let interpreter = Interpreter::new().with_capabilities(capabilities);
+    let result = interpreter.eval_program(program);
+    for diagnostic in interpreter.diagnostics() {
+        eprintln!("{}: {}", file, diagnostic);
+    }
+    match result {
+        Ok(value) => {
+            println!("{}", value);
+            0
+        }
+        Err(error) => {
+            eprintln!("{}: {}", file, error);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::runner::run;
+
+    #[test]
+    fn test_runtime_error_exit_code() {
+        let path = std::env::temp_dir().join(format!("eva-run-{}.eva", std::process::id()));
+        fs::write(&path, "(var x 1)\n(+ missing 1)\n").unwrap();
+        assert_eq!(run(&[path.display().to_string()]), 1);
+        fs::write(&path, "(var x 1)\n(+ x 1)\n").unwrap();
+        assert_eq!(run(&[path.display().to_string(), "--no-optimize".to_owned()]), 0);
+        fs::remove_file(path).unwrap();
+    }
+}
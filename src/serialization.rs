@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::EvalDataType;
+
+// Errors and functions have no counterpart in data formats, so serializing one fails instead of guessing a shape.
+// Numbers past `u64::MAX` fail too: formats such as JSON read them back as floats.
+impl Serialize for EvalDataType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            EvalDataType::String(text) => serializer.serialize_str(text),
+            EvalDataType::Number(number) => match u64::try_from(*number) {
+                Ok(number) => serializer.serialize_u64(number),
+                Err(_) => Err(ser::Error::custom(format!("cannot serialize number {}, which is larger than {}", number, u64::MAX))),
+            },
+            EvalDataType::Bool(value) => serializer.serialize_bool(*value),
+            EvalDataType::List(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            EvalDataType::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            EvalDataType::Null => serializer.serialize_unit(),
+            EvalDataType::Error(error) => Err(ser::Error::custom(format!("cannot serialize error value `{}`", error))),
+            EvalDataType::Function(function) => Err(ser::Error::custom(format!("cannot serialize function `{}`", function.name))),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = EvalDataType;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a string, non-negative integer, bool, list, map or null")
+    }
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<EvalDataType, E> {
+        Ok(EvalDataType::Bool(value))
+    }
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<EvalDataType, E> {
+        Ok(EvalDataType::Number(value.into()))
+    }
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<EvalDataType, E> {
+        Ok(EvalDataType::Number(value))
+    }
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<EvalDataType, E> {
+        u128::try_from(value).map(EvalDataType::Number).map_err(|_| E::custom(format!("negative number {} is not supported", value)))
+    }
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<EvalDataType, E> {
+        Err(E::custom(format!("non-integer number {} is not supported", value)))
+    }
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<EvalDataType, E> {
+        Ok(EvalDataType::String(value.to_owned()))
+    }
+    fn visit_string<E: de::Error>(self, value: String) -> Result<EvalDataType, E> {
+        Ok(EvalDataType::String(value))
+    }
+    fn visit_unit<E: de::Error>(self) -> Result<EvalDataType, E> {
+        Ok(EvalDataType::Null)
+    }
+    fn visit_none<E: de::Error>(self) -> Result<EvalDataType, E> {
+        Ok(EvalDataType::Null)
+    }
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<EvalDataType, D::Error> {
+        EvalDataType::deserialize(deserializer)
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<EvalDataType, A::Error> {
+        let mut items = vec![];
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(EvalDataType::List(items))
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<EvalDataType, A::Error> {
+        let mut entries = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry::<String, EvalDataType>()? {
+            entries.insert(key, value);
+        }
+        Ok(EvalDataType::Map(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for EvalDataType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<EvalDataType, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use crate::eval_error::{ErrorKind, EvalError};
+    use crate::EvalDataType;
+
+    #[test]
+    fn test_round_trip() {
+        let value = EvalDataType::Map(BTreeMap::from([
+            ("name".to_owned(), EvalDataType::from("eva")),
+            ("tags".to_owned(), EvalDataType::List(vec![EvalDataType::Number(1), EvalDataType::Bool(false), EvalDataType::Null])),
+        ]));
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"{"name":"eva","tags":[1,false,null]}"#);
+        assert_eq!(serde_json::from_str::<EvalDataType>(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_unsupported_values() {
+        let error = EvalDataType::Error(EvalError::new(ErrorKind::NotFound, "missing".to_owned()));
+        assert_eq!(serde_json::to_string(&error).unwrap_err().to_string(), "cannot serialize error value `not-found: missing`");
+        let largest = EvalDataType::Number(u64::MAX.into());
+        assert_eq!(serde_json::from_str::<EvalDataType>(&serde_json::to_string(&largest).unwrap()).unwrap(), largest);
+        assert_eq!(serde_json::to_string(&EvalDataType::Number(u128::from(u64::MAX) + 1)).unwrap_err().to_string(),
+            "cannot serialize number 18446744073709551616, which is larger than 18446744073709551615");
+        assert!(serde_json::from_str::<EvalDataType>("-1").unwrap_err().to_string().starts_with("negative number -1 is not supported"));
+        assert!(serde_json::from_str::<EvalDataType>("1.5").unwrap_err().to_string().starts_with("non-integer number 1.5 is not supported"));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::eval_error::{ErrorKind, EvalError};
+use crate::EvalDataType;
+
+pub const STRING_BUILTINS: [&str; 12] = [
+    "str-len", "substr", "index-of", "contains?", "starts-with?", "split", "join",
+    "trim", "upper", "lower", "replace", "format",
+];
+
+pub fn is_string_builtin(name: &str) -> bool {
+    STRING_BUILTINS.contains(&name)
+}
+
+pub fn call(name: &str, args: &[EvalDataType]) -> EvalDataType {
+    let result = match name {
+        "str-len" => str_len(args),
+        "substr" => substr(args),
+        "index-of" => index_of(args),
+        "contains?" => with_two_strings(name, args, |text, needle| EvalDataType::Bool(text.contains(needle))),
+        "starts-with?" => with_two_strings(name, args, |text, prefix| EvalDataType::Bool(text.starts_with(prefix))),
+        "split" => split(args),
+        "join" => join(args),
+        "trim" => with_string(name, args, |text| EvalDataType::String(text.trim().to_owned())),
+        "upper" => with_string(name, args, |text| EvalDataType::String(text.to_uppercase())),
+        "lower" => with_string(name, args, |text| EvalDataType::String(text.to_lowercase())),
+        "replace" => replace(args),
+        "format" => format(args),
+        _ => Err(EvalError::new(ErrorKind::NotFound, format!("`{}` is not a string built-in", name))),
+    };
+    result.unwrap_or_else(EvalDataType::Error)
+}
+
+pub fn expect_arity(name: &str, args: &[EvalDataType], min: usize, max: usize) -> Result<(), EvalError> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max { min.to_string() } else { format!("{} to {}", min, max) };
+        return Err(EvalError::new(ErrorKind::Arity, format!("`{}` expects {} arguments, got {}", name, expected, args.len())));
+    }
+    Ok(())
+}
+
+pub fn expect_string<'a>(name: &str, value: &'a EvalDataType) -> Result<&'a str, EvalError> {
+    match value {
+        EvalDataType::String(text) => Ok(text),
+        other => Err(EvalError::new(ErrorKind::TypeMismatch, format!("`{}` expects a string, got {:?}", name, other))),
+    }
+}
+
+pub fn expect_index(name: &str, value: &EvalDataType) -> Result<usize, EvalError> {
+    match value {
+        EvalDataType::Number(number) => usize::try_from(*number)
+            .map_err(|_| EvalError::new(ErrorKind::IndexOutOfRange, format!("`{}` index {} is out of range", name, number))),
+        other => Err(EvalError::new(ErrorKind::TypeMismatch, format!("`{}` expects a number, got {:?}", name, other))),
+    }
+}
+
+fn with_string(name: &str, args: &[EvalDataType], operation: impl Fn(&str) -> EvalDataType) -> Result<EvalDataType, EvalError> {
+    expect_arity(name, args, 1, 1)?;
+    Ok(operation(expect_string(name, &args[0])?))
+}
+
+fn with_two_strings(name: &str, args: &[EvalDataType], operation: impl Fn(&str, &str) -> EvalDataType) -> Result<EvalDataType, EvalError> {
+    expect_arity(name, args, 2, 2)?;
+    Ok(operation(expect_string(name, &args[0])?, expect_string(name, &args[1])?))
+}
+
+fn str_len(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    with_string("str-len", args, |text| EvalDataType::Number(text.chars().count() as u128))
+}
+
+// `(substr text start)` or `(substr text start end)`, with `end` exclusive and both counted in chars.
+fn substr(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("substr", args, 2, 3)?;
+    let chars: Vec<char> = expect_string("substr", &args[0])?.chars().collect();
+    let start = expect_index("substr", &args[1])?;
+    let end = match args.get(2) {
+        Some(end) => expect_index("substr", end)?,
+        None => chars.len(),
+    };
+    if start > end || end > chars.len() {
+        return Err(EvalError::new(ErrorKind::IndexOutOfRange, format!("`substr` range {}..{} is out of range for a string of length {}", start, end, chars.len())));
+    }
+    Ok(EvalDataType::String(chars[start..end].iter().collect()))
+}
+
+fn index_of(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("index-of", args, 2, 2)?;
+    let text = expect_string("index-of", &args[0])?;
+    let needle = expect_string("index-of", &args[1])?;
+    match text.find(needle) {
+        Some(byte_index) => Ok(EvalDataType::Number(text[..byte_index].chars().count() as u128)),
+        None => Err(EvalError::new(ErrorKind::NotFound, format!("`index-of` could not find {:?}", needle))),
+    }
+}
+
+fn split(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("split", args, 2, 2)?;
+    let text = expect_string("split", &args[0])?;
+    let separator = expect_string("split", &args[1])?;
+    let parts = if separator.is_empty() {
+        text.chars().map(|c| EvalDataType::String(c.to_string())).collect()
+    } else {
+        text.split(separator).map(|part| EvalDataType::String(part.to_owned())).collect()
+    };
+    Ok(EvalDataType::List(parts))
+}
+
+fn join(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("join", args, 2, 2)?;
+    let separator = expect_string("join", &args[1])?;
+    match &args[0] {
+        EvalDataType::List(items) => {
+            let parts = items.iter().map(|item| expect_string("join", item)).collect::<Result<Vec<&str>, EvalError>>()?;
+            Ok(EvalDataType::String(parts.join(separator)))
+        }
+        other => Err(EvalError::new(ErrorKind::TypeMismatch, format!("`join` expects a list, got {:?}", other))),
+    }
+}
+
+fn replace(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("replace", args, 3, 3)?;
+    let text = expect_string("replace", &args[0])?;
+    let from = expect_string("replace", &args[1])?;
+    let to = expect_string("replace", &args[2])?;
+    if from.is_empty() {
+        return Err(EvalError::new(ErrorKind::InvalidFormat, "`replace` cannot replace an empty string".to_owned()));
+    }
+    Ok(EvalDataType::String(text.replace(from, to)))
+}
+
+pub fn to_text(value: &EvalDataType) -> String {
+    match value {
+        EvalDataType::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+// `{}` takes the next argument, `{N}` takes argument N, and `{{`/`}}` are literal braces.
+fn format(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    if args.is_empty() {
+        return Err(EvalError::new(ErrorKind::Arity, "`format` expects a template".to_owned()));
+    }
+    let template = expect_string("format", &args[0])?;
+    let values = &args[1..];
+    let mut output = String::new();
+    let mut next = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let mut position = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(digit) if digit.is_ascii_digit() => position.push(digit),
+                        _ => return Err(EvalError::new(ErrorKind::InvalidFormat, format!("`format` has a malformed placeholder in {:?}", template))),
+                    }
+                }
+                let index = if position.is_empty() {
+                    next += 1;
+                    next - 1
+                } else {
+                    position.parse::<usize>().map_err(|_| EvalError::new(ErrorKind::IndexOutOfRange, format!("`format` placeholder {{{}}} is out of range", position)))?
+                };
+                match values.get(index) {
+                    Some(value) => output.push_str(&to_text(value)),
+                    None => return Err(EvalError::new(ErrorKind::IndexOutOfRange, format!("`format` placeholder {} has no matching argument ({} given)", index, values.len()))),
+                }
+            }
+            '}' => return Err(EvalError::new(ErrorKind::InvalidFormat, format!("`format` has an unmatched `}}` in {:?}", template))),
+            c => output.push(c),
+        }
+    }
+    Ok(EvalDataType::String(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval_error::ErrorKind;
+    use crate::string_library::call;
+    use crate::EvalDataType;
+
+    fn text(value: &str) -> EvalDataType {
+        EvalDataType::String(value.to_owned())
+    }
+
+    fn error_kind(value: EvalDataType) -> ErrorKind {
+        match value {
+            EvalDataType::Error(error) => error.kind,
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_length_and_slicing_use_chars() {
+        assert_eq!(call("str-len", &[text("héllo wörld")]), EvalDataType::Number(11));
+        assert_eq!(call("substr", &[text("héllo wörld"), EvalDataType::Number(6)]), text("wörld"));
+        assert_eq!(call("substr", &[text("héllo"), EvalDataType::Number(1), EvalDataType::Number(3)]), text("él"));
+        assert_eq!(call("index-of", &[text("日本語テキスト"), text("テ")]), EvalDataType::Number(3));
+    }
+
+    #[test]
+    fn test_out_of_range_is_an_error_value() {
+        assert_eq!(error_kind(call("substr", &[text("abc"), EvalDataType::Number(2), EvalDataType::Number(5)])), ErrorKind::IndexOutOfRange);
+        assert_eq!(error_kind(call("substr", &[text("abc"), EvalDataType::Number(3), EvalDataType::Number(1)])), ErrorKind::IndexOutOfRange);
+        assert_eq!(error_kind(call("index-of", &[text("abc"), text("z")])), ErrorKind::NotFound);
+        assert_eq!(error_kind(call("str-len", &[EvalDataType::Number(1)])), ErrorKind::TypeMismatch);
+        assert_eq!(error_kind(call("trim", &[])), ErrorKind::Arity);
+    }
+
+    #[test]
+    fn test_searching() {
+        assert_eq!(call("contains?", &[text("hello"), text("ell")]), EvalDataType::Bool(true));
+        assert_eq!(call("contains?", &[text("hello"), text("xyz")]), EvalDataType::Bool(false));
+        assert_eq!(call("starts-with?", &[text("hello"), text("he")]), EvalDataType::Bool(true));
+        assert_eq!(call("starts-with?", &[text("hello"), text("lo")]), EvalDataType::Bool(false));
+    }
+
+    #[test]
+    fn test_split_and_join() {
+        let parts = call("split", &[text("a,b,,c"), text(",")]);
+        assert_eq!(parts, EvalDataType::List(vec![text("a"), text("b"), text(""), text("c")]));
+        assert_eq!(call("join", &[parts, text("-")]), text("a-b--c"));
+        assert_eq!(call("split", &[text("añb"), text("")]), EvalDataType::List(vec![text("a"), text("ñ"), text("b")]));
+        assert_eq!(error_kind(call("join", &[EvalDataType::List(vec![EvalDataType::Number(1)]), text(",")])), ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_trim_case_and_replace() {
+        assert_eq!(call("trim", &[text("  padded \n")]), text("padded"));
+        assert_eq!(call("upper", &[text("straße")]), text("STRASSE"));
+        assert_eq!(call("lower", &[text("ÀÉÎ")]), text("àéî"));
+        assert_eq!(call("replace", &[text("a-b-c"), text("-"), text("+")]), text("a+b+c"));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(call("format", &[text("Hello {}!"), text("Eva")]), text("Hello Eva!"));
+        assert_eq!(call("format", &[text("{1} {0} {}"), text("a"), EvalDataType::Number(2)]), text("2 a a"));
+        assert_eq!(call("format", &[text("{{{}}}"), EvalDataType::Bool(true)]), text("{true}"));
+        assert_eq!(error_kind(call("format", &[text("{} {}"), text("a")])), ErrorKind::IndexOutOfRange);
+        assert_eq!(error_kind(call("format", &[text("{x}"), text("a")])), ErrorKind::InvalidFormat);
+    }
+
+    #[test]
+    fn test_bound_names_shadow_built_ins() {
+        let interpreter = crate::Interpreter::new();
+        assert_eq!(interpreter.eval_str("(def trim (s) (upper s)) (trim \" a \")"), Ok(text(" A ")));
+        assert_eq!(interpreter.eval_str("(var upper 5) upper"), Ok(EvalDataType::Number(5)));
+    }
+}
@@ -0,0 +1,115 @@
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::eval_error::{ErrorKind, EvalError};
+use crate::string_library::{expect_arity, expect_string};
+use crate::EvalDataType;
+
+pub const SYSTEM_BUILTINS: [&str; 4] = ["env-var", "now", "random", "process-run"];
+
+pub fn is_system_builtin(name: &str) -> bool {
+    SYSTEM_BUILTINS.contains(&name)
+}
+
+/// A xorshift64* generator for `random`; fast and good enough for scripts, but not for secrets.
+pub struct Random(Cell<u64>);
+
+impl Default for Random {
+    fn default() -> Random {
+        Random::new()
+    }
+}
+
+impl Random {
+    pub fn new() -> Random {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos()));
+        Random(Cell::new(hasher.finish() | 1))
+    }
+    fn next(&self) -> u64 {
+        let mut state = self.0.get();
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        self.0.set(state);
+        state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    pub fn below(&self, bound: u128) -> u128 {
+        ((self.next() as u128) << 64 | self.next() as u128) % bound
+    }
+}
+
+pub fn call(name: &str, args: &[EvalDataType], random: &Random) -> EvalDataType {
+    let result = match name {
+        "env-var" => env_var(args),
+        "now" => now(args),
+        "random" => random_below(args, random),
+        "process-run" => process_run(args),
+        _ => Err(EvalError::new(ErrorKind::NotFound, format!("`{}` is not a system built-in", name))),
+    };
+    result.unwrap_or_else(EvalDataType::Error)
+}
+
+// The value of an environment variable, or `null` when it is unset or not valid UTF-8.
+fn env_var(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("env-var", args, 1, 1)?;
+    Ok(std::env::var(expect_string("env-var", &args[0])?).map_or(EvalDataType::Null, EvalDataType::String))
+}
+
+// Milliseconds since the Unix epoch.
+fn now(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    expect_arity("now", args, 0, 0)?;
+    Ok(EvalDataType::Number(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis())))
+}
+
+// `(random n)`: a number from 0 up to, but not including, `n`.
+fn random_below(args: &[EvalDataType], random: &Random) -> Result<EvalDataType, EvalError> {
+    expect_arity("random", args, 1, 1)?;
+    match &args[0] {
+        EvalDataType::Number(bound) if *bound > 0 => Ok(EvalDataType::Number(random.below(*bound))),
+        other => Err(EvalError::new(ErrorKind::TypeMismatch, format!("`random` expects a positive number, got {}", other))),
+    }
+}
+
+// `(process-run program arg…)` waits for the program and returns its exit status (`null` when it was killed by a
+// signal) with its captured output.
+fn process_run(args: &[EvalDataType]) -> Result<EvalDataType, EvalError> {
+    if args.is_empty() {
+        return Err(EvalError::new(ErrorKind::Arity, "`process-run` expects a program".to_owned()));
+    }
+    let program = expect_string("process-run", &args[0])?;
+    let arguments = args[1..].iter().map(|arg| expect_string("process-run", arg)).collect::<Result<Vec<&str>, EvalError>>()?;
+    let output = Command::new(program).args(arguments).output()
+        .map_err(|error| EvalError::new(ErrorKind::Io, format!("`process-run` {:?}: {}", program, error)))?;
+    let status = output.status.code().and_then(|code| u128::try_from(code).ok()).map_or(EvalDataType::Null, EvalDataType::Number);
+    Ok(EvalDataType::Map(BTreeMap::from([
+        ("status".to_owned(), status),
+        ("stdout".to_owned(), EvalDataType::String(String::from_utf8_lossy(&output.stdout).into_owned())),
+        ("stderr".to_owned(), EvalDataType::String(String::from_utf8_lossy(&output.stderr).into_owned())),
+    ])))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::capabilities::Capabilities;
+    use crate::{EvalDataType, Interpreter};
+
+    #[test]
+    fn test_process_run() {
+        let interpreter = Interpreter::new().with_capabilities(Capabilities::none().process());
+        assert_eq!(interpreter.eval_str("(get (process-run \"echo\" \"hello\" \"eva\") \"stdout\")"), Ok(EvalDataType::from("hello eva\n")));
+        assert_eq!(interpreter.eval_str("(get (process-run \"false\") \"status\")"), Ok(EvalDataType::Number(1)));
+        assert!(matches!(interpreter.eval_str("(process-run \"/nonexistent/eva\")"), Ok(EvalDataType::Error(_))));
+    }
+
+    #[test]
+    fn test_bound_names_shadow_built_ins() {
+        let interpreter = Interpreter::new().with_capabilities(Capabilities::none().clock().process());
+        assert_eq!(interpreter.eval_str("(var now 5) now"), Ok(EvalDataType::Number(5)));
+        assert_eq!(interpreter.eval_str("(var gc 6) gc"), Ok(EvalDataType::Number(6)));
+        assert_eq!(interpreter.eval_str("(def process-run (x) x) (process-run 7)"), Ok(EvalDataType::Number(7)));
+    }
+}
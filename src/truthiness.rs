@@ -0,0 +1,44 @@
+use crate::EvalDataType;
+
+/// How the conditions of `if`, `while`, `when`, `unless` and `cond` are read.
+///
+/// `Strict`, the default, only accepts booleans and fails on anything else, so a forgotten comparison is caught
+/// where it happens. `Lenient` treats `null` and `false` as false and every other value, `0` and `""` included,
+/// as true.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Truthiness {
+    Strict,
+    Lenient,
+}
+
+impl Truthiness {
+    pub fn test(&self, value: &EvalDataType) -> Result<bool, String> {
+        match (self, value) {
+            (_, EvalDataType::Bool(value)) => Ok(*value),
+            (Truthiness::Lenient, EvalDataType::Null) => Ok(false),
+            (Truthiness::Lenient, _) => Ok(true),
+            (Truthiness::Strict, other) => Err(format!("condition must be a bool, got {}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::truthiness::Truthiness;
+    use crate::EvalDataType;
+
+    #[test]
+    fn test_strict_only_accepts_bools() {
+        assert_eq!(Truthiness::Strict.test(&EvalDataType::Bool(false)), Ok(false));
+        assert_eq!(Truthiness::Strict.test(&EvalDataType::Null), Err("condition must be a bool, got null".to_owned()));
+        assert_eq!(Truthiness::Strict.test(&EvalDataType::Number(1)), Err("condition must be a bool, got 1".to_owned()));
+    }
+
+    #[test]
+    fn test_lenient_falsy_values() {
+        assert_eq!(Truthiness::Lenient.test(&EvalDataType::Null), Ok(false));
+        assert_eq!(Truthiness::Lenient.test(&EvalDataType::Bool(false)), Ok(false));
+        assert_eq!(Truthiness::Lenient.test(&EvalDataType::Number(0)), Ok(true));
+        assert_eq!(Truthiness::Lenient.test(&EvalDataType::String(String::new())), Ok(true));
+    }
+}
@@ -0,0 +1,624 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use crate::function::Signature;
+use crate::parser::{lower, parse_syntax, SyntaxNode};
+use crate::pattern::{clause_parts, Pattern, VarTarget};
+use crate::printer::print_inline;
+use crate::types::{FunctionType, Type};
+use crate::{get_environment_manager, Eva, EvalDataType, EvalType, Span};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct TypeError {
+    pub message: String,
+    pub expression: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in `{}`", self.message, self.expression)
+    }
+}
+
+struct BuiltinSignature {
+    params: Vec<Type>,
+    optional: usize,
+    rest: Option<Type>,
+    result: Type,
+}
+
+fn builtin_signature(name: &str) -> Option<BuiltinSignature> {
+    let fixed = |params: Vec<Type>, result: Type| Some(BuiltinSignature { params, optional: 0, rest: None, result });
+    let string_or_error = Type::union(vec![Type::String, Type::Error]);
+    match name {
+        "str-len" => fixed(vec![Type::String], Type::Number),
+        "substr" => Some(BuiltinSignature { params: vec![Type::String, Type::Number, Type::Number], optional: 1, rest: None, result: string_or_error }),
+        "index-of" => fixed(vec![Type::String, Type::String], Type::union(vec![Type::Number, Type::Error])),
+        "contains?" | "starts-with?" => fixed(vec![Type::String, Type::String], Type::Bool),
+        "split" => fixed(vec![Type::String, Type::String], Type::List),
+        "join" => fixed(vec![Type::List, Type::String], Type::String),
+        "trim" | "upper" | "lower" => fixed(vec![Type::String], Type::String),
+        "replace" => fixed(vec![Type::String, Type::String, Type::String], Type::String),
+        "format" => Some(BuiltinSignature { params: vec![Type::String], optional: 0, rest: Some(Type::Any), result: Type::String }),
+        "to-string" | "type-of" => fixed(vec![Type::Any], Type::String),
+        "to-number" => fixed(vec![Type::Any], Type::union(vec![Type::Number, Type::Error])),
+        "to-bool" => fixed(vec![Type::Any], Type::union(vec![Type::Bool, Type::Error])),
+        "number?" | "string?" | "bool?" | "list?" | "error?" | "function?" | "map?" | "null?" => fixed(vec![Type::Any], Type::Bool),
+        "json-parse" => fixed(vec![Type::String], Type::Any),
+        "json-stringify" => fixed(vec![Type::Any], string_or_error),
+        "get" => fixed(vec![Type::union(vec![Type::Map, Type::List]), Type::union(vec![Type::String, Type::Number])], Type::Any),
+        "keys" => fixed(vec![Type::Map], Type::union(vec![Type::List, Type::Error])),
+        "gc" => fixed(vec![], Type::Map),
+        "print" | "println" | "eprint" => Some(BuiltinSignature { params: vec![], optional: 0, rest: Some(Type::Any), result: Type::union(vec![Type::Null, Type::Error]) }),
+        "file-read" => fixed(vec![Type::String], string_or_error),
+        "file-write" | "file-append" => fixed(vec![Type::String, Type::String], Type::union(vec![Type::Null, Type::Error])),
+        "file-exists?" => fixed(vec![Type::String], Type::union(vec![Type::Bool, Type::Error])),
+        "list-dir" => Some(BuiltinSignature { params: vec![Type::String], optional: 1, rest: None, result: Type::union(vec![Type::List, Type::Error]) }),
+        "delete-file" => fixed(vec![Type::String], Type::union(vec![Type::Null, Type::Error])),
+        "env-var" => fixed(vec![Type::String], Type::union(vec![Type::String, Type::Null, Type::Error])),
+        "now" => fixed(vec![], Type::union(vec![Type::Number, Type::Error])),
+        "random" => fixed(vec![Type::Number], Type::union(vec![Type::Number, Type::Error])),
+        "process-run" => Some(BuiltinSignature { params: vec![Type::String], optional: 0, rest: Some(Type::String), result: Type::union(vec![Type::Map, Type::Error]) }),
+        "read-line" => fixed(vec![], Type::union(vec![Type::String, Type::Null, Type::Error])),
+        _ => None,
+    }
+}
+
+/// Infers and checks types over the same trees `Eva::eval` runs. Un-annotated variables and parameters are `any`,
+/// so only annotations and literals constrain a program.
+pub struct TypeChecker {
+    scopes: Vec<HashMap<String, Type>>,
+    errors: Vec<TypeError>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> TypeChecker {
+        TypeChecker::new()
+    }
+}
+
+impl TypeChecker {
+    pub fn new() -> TypeChecker {
+        let globals = get_environment_manager().borrow().env.iter()
+            .map(|(name, value)| (name.clone(), Type::of_value(value)))
+            .collect();
+        TypeChecker { scopes: vec![globals, HashMap::new()], errors: vec![] }
+    }
+    pub fn check(&mut self, exp: &EvalType) -> Type {
+        self.check_expression(exp)
+    }
+    pub fn take_errors(&mut self) -> Vec<TypeError> {
+        std::mem::take(&mut self.errors)
+    }
+    /// Adds a scope of already known names, as for checking one form in the middle of a program.
+    pub(crate) fn with_scope(mut self, scope: HashMap<String, Type>) -> TypeChecker {
+        self.scopes.push(scope);
+        self
+    }
+    pub(crate) fn lookup(&self, name: &str) -> Option<Type> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).cloned())
+    }
+    fn declare(&mut self, name: &str, declared: Type) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned(), declared);
+        }
+    }
+    fn error(&mut self, message: String, form: &[EvalType]) {
+        self.errors.push(TypeError { message, expression: print_inline(&EvalType::Content(form.to_vec(), Span::NONE)) });
+    }
+    fn expect(&mut self, actual: &Type, expected: &Type, what: String, form: &[EvalType]) {
+        if !actual.is_assignable_to(expected) {
+            self.error(format!("{} expects {}, found {}", what, expected, actual), form);
+        }
+    }
+    fn check_expression(&mut self, exp: &EvalType) -> Type {
+        match exp {
+            EvalType::Content(items, _) => self.check_form(items),
+            // Checked as a one-item form, but reported as written rather than as a call.
+            value => {
+                let reported = self.errors.len();
+                let result = self.check_form(std::slice::from_ref(value));
+                self.errors[reported..].iter_mut().for_each(|error| error.expression = print_inline(value));
+                result
+            }
+        }
+    }
+    // A value the runtime evaluates from a slice of a form, such as an operand or what `var` binds.
+    fn check_value(&mut self, items: &[EvalType]) -> Type {
+        match items {
+            [single] => self.check_expression(single),
+            items => self.check_form(items),
+        }
+    }
+    // Mirrors `Eva::eval`: a form whose head is itself a list only evaluates that list.
+    fn check_form(&mut self, items: &[EvalType]) -> Type {
+        match items.first() {
+            None => Type::Any,
+            Some(EvalType::Content(inner, _)) => self.check_form(inner),
+            Some(EvalType::Value(EvalDataType::String(head))) => self.check_operation(head, &items[1..], items),
+            Some(EvalType::Value(value)) => Type::of_value(value),
+        }
+    }
+    fn check_operation(&mut self, head: &str, args: &[EvalType], form: &[EvalType]) -> Type {
+        match head {
+            "+" | "*" | "/" | ">" | "<" => self.check_arithmetic(head, args, form),
+            "??" => self.check_default(args, form),
+            "var" => self.check_var(args, form),
+            "const" => self.check_const(args, form),
+            "set" => self.check_set(args, form),
+            "if" => self.check_if(args, form),
+            "when" | "unless" => self.check_when(head, args, form),
+            "cond" => self.check_cond(args, form),
+            "while" => self.check_while(args, form),
+            "begin" => {
+                self.scopes.push(HashMap::new());
+                let result = Eva::block_statements(args).iter().fold(Type::Null, |_, statement| self.check_expression(statement));
+                self.scopes.pop();
+                result
+            }
+            "let" | "let*" | "letrec" => self.check_let(head, args, form),
+            "match" => self.check_match(args, form),
+            "def" => match args.first() {
+                Some(EvalType::Value(EvalDataType::String(name))) => {
+                    let name = name.clone();
+                    self.check_function(Some(&name), &args[1..], form)
+                }
+                _ => {
+                    self.error("`def` expects a name".to_owned(), form);
+                    Type::Any
+                }
+            },
+            "lambda" => self.check_function(None, args, form),
+            literal if literal.len() >= 2 && literal.starts_with('\'') && literal.ends_with('\'') => Type::String,
+            name => match builtin_signature(name).filter(|_| self.lookup(name).is_none()) {
+                Some(signature) => self.check_builtin_call(name, signature, args, form),
+                None => self.check_variable(name, args, form),
+            },
+        }
+    }
+    fn check_arithmetic(&mut self, operator: &str, args: &[EvalType], form: &[EvalType]) -> Type {
+        if args.len() < 2 {
+            self.error(format!("`{}` expects two operands", operator), form);
+            return Type::Any;
+        }
+        let first = self.check_value(&args[0..1]);
+        let second = self.check_value(&args[1..]);
+        let both = |expected: &Type| first.is_assignable_to(expected) && second.is_assignable_to(expected);
+        let dynamic = first == Type::Any && second == Type::Any;
+        match operator {
+            "+" if both(&Type::Number) => if dynamic { Type::Any } else { Type::Number },
+            "+" if both(&Type::String) => Type::String,
+            "+" => {
+                self.error(format!("`+` expects two numbers or two strings, found {} and {}", first, second), form);
+                Type::Any
+            }
+            operator => {
+                if !both(&Type::Number) {
+                    self.error(format!("`{}` expects two numbers, found {} and {}", operator, first, second), form);
+                }
+                if operator == ">" || operator == "<" { Type::Bool } else { Type::Number }
+            }
+        }
+    }
+    // The result is the value's type without null, or the fallback's type.
+    fn check_default(&mut self, args: &[EvalType], form: &[EvalType]) -> Type {
+        if args.len() < 2 {
+            self.error("`??` expects a value and a fallback".to_owned(), form);
+            return Type::Any;
+        }
+        let value = self.check_value(&args[0..1]);
+        let fallback = self.check_value(&args[1..]);
+        match value {
+            Type::Null => fallback,
+            Type::Union(members) if members.contains(&Type::Null) => {
+                Type::union(members.into_iter().filter(|member| *member != Type::Null).chain([fallback]).collect())
+            }
+            Type::Any => Type::Any,
+            value => value,
+        }
+    }
+    fn check_var(&mut self, args: &[EvalType], form: &[EvalType]) -> Type {
+        let value = self.check_value(&args[1.min(args.len())..]);
+        let target = match args.first().map(VarTarget::parse) {
+            Some(Ok(target)) => target,
+            Some(Err(message)) => {
+                self.error(message, form);
+                return Type::Any;
+            }
+            None => {
+                self.error("`var` expects a name, (name : type) or a pattern".to_owned(), form);
+                return Type::Any;
+            }
+        };
+        match target {
+            VarTarget::Name(name, None) => self.declare(&name, Type::Any),
+            VarTarget::Name(name, Some(declared)) => {
+                self.expect(&value, &declared, format!("variable `{}`", name), form);
+                self.declare(&name, declared);
+            }
+            VarTarget::Pattern(pattern) => self.check_pattern(&pattern, &value, form),
+        }
+        value
+    }
+    // Pattern variables are untyped; only the shape of the outermost pattern is checked against the value.
+    fn check_pattern(&mut self, pattern: &Pattern, value: &Type, form: &[EvalType]) {
+        match pattern {
+            Pattern::List { .. } => self.expect(value, &Type::List, "pattern".to_owned(), form),
+            Pattern::Map(_) => self.expect(value, &Type::Map, "pattern".to_owned(), form),
+            _ => {}
+        }
+        for name in pattern.names() {
+            self.declare(&name, Type::Any);
+        }
+    }
+    fn check_match(&mut self, args: &[EvalType], form: &[EvalType]) -> Type {
+        match args.first() {
+            Some(value) => self.check_expression(value),
+            None => {
+                self.error("`match` expects a value and clauses".to_owned(), form);
+                return Type::Any;
+            }
+        };
+        let mut results = vec![];
+        let mut exhaustive = false;
+        for clause in &args[1..] {
+            let (pattern, guard, body) = match clause_parts(clause).map(|(pattern, guard, body)| (Pattern::parse(pattern), guard, body)) {
+                Some((Ok(pattern), guard, body)) => (pattern, guard, body),
+                Some((Err(message), _, _)) => {
+                    self.error(message, form);
+                    continue;
+                }
+                None => {
+                    self.error("`match` clause must be (pattern [when guard] body…)".to_owned(), form);
+                    continue;
+                }
+            };
+            self.scopes.push(pattern.names().into_iter().map(|name| (name, Type::Any)).collect());
+            if let Some(guard) = guard {
+                self.check_condition("match", guard, form);
+            }
+            results.push(self.check_statements(body));
+            self.scopes.pop();
+            exhaustive |= guard.is_none() && pattern.is_irrefutable();
+        }
+        if !exhaustive {
+            results.push(Type::Null);
+        }
+        Type::union(results)
+    }
+    // A constant never changes, so it keeps the type inferred from its value.
+    fn check_const(&mut self, args: &[EvalType], form: &[EvalType]) -> Type {
+        let value = self.check_value(&args[1.min(args.len())..]);
+        match args.first() {
+            Some(EvalType::Value(EvalDataType::String(name))) => self.declare(name, value.clone()),
+            _ => self.error("`const` expects a name".to_owned(), form),
+        }
+        value
+    }
+    fn check_set(&mut self, args: &[EvalType], form: &[EvalType]) -> Type {
+        let value = self.check_value(&args[1.min(args.len())..]);
+        match args.first() {
+            Some(EvalType::Value(EvalDataType::String(name))) => match self.lookup(name) {
+                Some(declared) => self.expect(&value, &declared, format!("variable `{}`", name), form),
+                None => self.error(format!("assignment to undeclared variable `{}`", name), form),
+            },
+            _ => self.error("`set` expects a name".to_owned(), form),
+        }
+        value
+    }
+    fn wrapped_parts(&mut self, keyword: &str, args: &[EvalType], count: usize, form: &[EvalType]) -> Option<Vec<EvalType>> {
+        match args.first() {
+            Some(EvalType::Content(parts, _)) if parts.len() >= count => Some(parts.clone()),
+            _ => {
+                self.error(format!("`{}` expects its {} parts wrapped in one list", keyword, count), form);
+                None
+            }
+        }
+    }
+    // Conditions may be null as well, which lenient truthiness reads as false.
+    fn check_condition(&mut self, keyword: &str, condition: &EvalType, form: &[EvalType]) {
+        let condition = self.check_expression(condition);
+        self.expect(&condition, &Type::union(vec![Type::Bool, Type::Null]), format!("`{}` condition", keyword), form);
+    }
+    fn check_statements(&mut self, statements: &[EvalType]) -> Type {
+        statements.iter().fold(Type::Null, |_, statement| self.check_expression(statement))
+    }
+    fn check_if(&mut self, args: &[EvalType], form: &[EvalType]) -> Type {
+        let parts = Eva::if_parts(args);
+        if parts.len() < 2 || parts.len() > 3 {
+            self.error(format!("`if` expects a condition, a branch and an optional else branch, found {} parts", parts.len()), form);
+            return Type::Any;
+        }
+        self.check_condition("if", &parts[0], form);
+        Type::union(vec![self.check_expression(&parts[1]), self.check_statements(&parts[2..])])
+    }
+    fn check_when(&mut self, keyword: &str, args: &[EvalType], form: &[EvalType]) -> Type {
+        match args.first() {
+            Some(condition) => self.check_condition(keyword, condition, form),
+            None => self.error(format!("`{}` expects a condition", keyword), form),
+        }
+        Type::union(vec![self.check_statements(&args[1.min(args.len())..]), Type::Null])
+    }
+    fn check_cond(&mut self, args: &[EvalType], form: &[EvalType]) -> Type {
+        let mut results = vec![];
+        let mut exhaustive = false;
+        for clause in args {
+            match clause {
+                EvalType::Content(clause, _) if clause.first() == Some(&EvalType::Value(EvalDataType::String("else".to_owned()))) => {
+                    results.push(self.check_statements(&clause[1..]));
+                    exhaustive = true;
+                }
+                EvalType::Content(clause, _) if !clause.is_empty() => {
+                    self.check_condition("cond", &clause[0], form);
+                    results.push(self.check_statements(&clause[1..]));
+                }
+                _ => self.error("`cond` expects clauses of the form (test body...)".to_owned(), form),
+            }
+        }
+        if !exhaustive {
+            results.push(Type::Null);
+        }
+        Type::union(results)
+    }
+    fn check_let(&mut self, kind: &str, args: &[EvalType], form: &[EvalType]) -> Type {
+        let (bindings, body) = match Eva::let_parts(args) {
+            Some(parts) => parts,
+            None => {
+                self.error(format!("`{}` expects a list of (name value) bindings", kind), form);
+                return Type::Any;
+            }
+        };
+        match kind {
+            "let" => {
+                let values: Vec<Type> = bindings.iter().map(|(_, value)| self.check_value(value)).collect();
+                self.scopes.push(HashMap::new());
+                for ((name, _), value) in bindings.iter().zip(values) {
+                    self.declare(name, value);
+                }
+            }
+            "let*" => {
+                self.scopes.push(HashMap::new());
+                for (name, value) in &bindings {
+                    let value = self.check_value(value);
+                    self.declare(name, value);
+                }
+            }
+            _ => {
+                self.scopes.push(bindings.iter().map(|(name, _)| (name.to_string(), Type::Any)).collect());
+                for (name, value) in &bindings {
+                    let value = self.check_value(value);
+                    self.declare(name, value);
+                }
+            }
+        }
+        let result = self.check_statements(body);
+        self.scopes.pop();
+        result
+    }
+    fn check_while(&mut self, args: &[EvalType], form: &[EvalType]) -> Type {
+        let parts = match self.wrapped_parts("while", args, 2, form) {
+            Some(parts) => parts,
+            None => return Type::Any,
+        };
+        let condition = self.check_expression(&parts[0]);
+        self.expect(&condition, &Type::union(vec![Type::Bool, Type::Null]), "`while` condition".to_owned(), form);
+        Type::union(vec![Type::Null, self.check_expression(&parts[1])])
+    }
+    fn check_function(&mut self, name: Option<&str>, args: &[EvalType], form: &[EvalType]) -> Type {
+        let signature = match Signature::parse(args) {
+            Ok(signature) => signature,
+            Err(message) => {
+                self.error(message, form);
+                return Type::Any;
+            }
+        };
+        let function_type = signature.function_type();
+        if let Some(name) = name {
+            self.declare(name, Type::Function(Some(function_type.clone())));
+        }
+        self.scopes.push(signature.params.iter().map(|param| (param.name.clone(), param.annotation.clone().unwrap_or(Type::Any))).collect());
+        let body = signature.body.iter().fold(Type::Any, |_, statement| self.check_expression(statement));
+        self.scopes.pop();
+        let result = match &signature.return_type {
+            Some(declared) => {
+                let what = format!("`{}` return value", name.unwrap_or("lambda"));
+                self.expect(&body, declared, what, form);
+                declared.clone()
+            }
+            None => body,
+        };
+        let function_type = Type::Function(Some(FunctionType { params: function_type.params, result: Box::new(result) }));
+        if let Some(name) = name {
+            self.declare(name, function_type.clone());
+        }
+        function_type
+    }
+    fn check_arguments(&mut self, callee: &str, params: &[Type], args: &[EvalType], form: &[EvalType]) {
+        for (index, arg) in args.iter().enumerate() {
+            let actual = self.check_expression(arg);
+            if let Some(expected) = params.get(index) {
+                self.expect(&actual, expected, format!("argument {} of `{}`", index + 1, callee), form);
+            }
+        }
+    }
+    fn check_builtin_call(&mut self, name: &str, signature: BuiltinSignature, args: &[EvalType], form: &[EvalType]) -> Type {
+        let required = signature.params.len() - signature.optional;
+        if args.len() < required || (signature.rest.is_none() && args.len() > signature.params.len()) {
+            self.error(format!("`{}` expects {} arguments, found {}", name, signature.params.len(), args.len()), form);
+        }
+        let mut params = signature.params.clone();
+        if let Some(rest) = &signature.rest {
+            params.resize(args.len().max(params.len()), rest.clone());
+        }
+        self.check_arguments(name, &params, args, form);
+        signature.result
+    }
+    fn check_variable(&mut self, name: &str, args: &[EvalType], form: &[EvalType]) -> Type {
+        match self.lookup(name) {
+            None => {
+                self.error(format!("undefined variable `{}`", name), form);
+                Type::Any
+            }
+            Some(Type::Function(Some(signature))) if !args.is_empty() || signature.params.is_empty() => {
+                if args.len() != signature.params.len() {
+                    self.error(format!("`{}` expects {} arguments, found {}", name, signature.params.len(), args.len()), form);
+                }
+                self.check_arguments(name, &signature.params, args, form);
+                *signature.result
+            }
+            Some(Type::Any | Type::Function(None)) if !args.is_empty() => {
+                self.check_arguments(name, &[], args, form);
+                Type::Any
+            }
+            Some(declared) => declared,
+        }
+    }
+}
+
+fn node_position(node: &SyntaxNode) -> (usize, usize) {
+    match node {
+        SyntaxNode::Atom { line, column, .. } | SyntaxNode::List { line, column, .. } => (*line, *column),
+        SyntaxNode::Comment { .. } | SyntaxNode::BlankLine => (0, 0),
+    }
+}
+
+/// `eva check files…`: type checks each file without running it and exits 1 on any mismatch.
+pub fn run(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("usage: eva check <files>...");
+        return 2;
+    }
+    let mut status = 0;
+    for file in args {
+        let nodes = match fs::read_to_string(file).map_err(|error| error.to_string())
+            .and_then(|source| parse_syntax(&source).map_err(|error| error.to_string())) {
+            Ok(nodes) => nodes,
+            Err(error) => {
+                eprintln!("{}: {}", file, error);
+                status = 2;
+                continue;
+            }
+        };
+        let mut checker = TypeChecker::new();
+        for node in &nodes {
+            let exp = match lower(node) {
+                Ok(Some(exp)) => exp,
+                Ok(None) => continue,
+                Err(error) => {
+                    eprintln!("{}:{}", file, error);
+                    status = 2;
+                    continue;
+                }
+            };
+            checker.check(&exp);
+            let (line, column) = node_position(node);
+            for error in checker.take_errors() {
+                println!("{}:{}:{}: error: {}", file, line, column, error);
+                status = status.max(1);
+            }
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse;
+    use crate::type_checker::TypeChecker;
+    use crate::types::Type;
+
+    fn check(source: &str) -> (Type, Vec<String>) {
+        let mut checker = TypeChecker::new();
+        let mut result = Type::Any;
+        for exp in parse(source).unwrap() {
+            result = checker.check(&exp);
+        }
+        (result, checker.take_errors().iter().map(|error| error.to_string()).collect())
+    }
+
+    #[test]
+    fn test_unannotated_code_checks_clean() {
+        let source = concat!(
+            "(begin ((var counter 0) (var result 0)",
+            " (while ((< counter 10) (begin ((set result (+ result 1)) (set counter (+ counter 1))))))",
+            " (set result \"done\") result))",
+        );
+        assert_eq!(check(source), (Type::Any, vec![]));
+    }
+
+    #[test]
+    fn test_literal_mismatch() {
+        assert_eq!(check("(+ \"a\" 1)").1, vec!["`+` expects two numbers or two strings, found string and number in `(+ \"a\" 1)`"]);
+        assert_eq!(check("(* 2 (> 1 0))").1, vec!["`*` expects two numbers, found number and bool in `(* 2 (> 1 0))`"]);
+        assert_eq!(check("(+ (str-len \"abc\") 1)"), (Type::Number, vec![]));
+    }
+
+    #[test]
+    fn test_annotated_variables() {
+        assert_eq!(check("(var (x : number) 10) (+ x 1)"), (Type::Number, vec![]));
+        assert_eq!(check("(var (x : number) \"ten\")").1, vec!["variable `x` expects number, found string in `(var (x : number) \"ten\")`"]);
+        assert_eq!(check("(var (x : number) 10) (set x true)").1, vec!["variable `x` expects number, found bool in `(set x true)`"]);
+        assert_eq!(check("(var (x : (or number string)) 10) (set x \"ten\") x").0, Type::Union(vec![Type::Number, Type::String]));
+        assert_eq!(check("(var (x : any) 10) (set x true)").1, Vec::<String>::new());
+        assert_eq!(check("(const limit 10) (+ limit \"px\")").1.len(), 1);
+    }
+
+    #[test]
+    fn test_annotated_functions() {
+        let square = "(def square ((x : number)) -> number (* x x))";
+        assert_eq!(check(&format!("{} (square 3)", square)), (Type::Number, vec![]));
+        assert_eq!(check(&format!("{} (square \"3\")", square)).1, vec!["argument 1 of `square` expects number, found string in `(square \"3\")`"]);
+        assert_eq!(check(&format!("{} (+ (square 3) \"px\")", square)).1.len(), 1);
+        assert_eq!(check("(def name () -> number \"eva\")").1, vec!["`name` return value expects number, found string in `(def name () -> number \"eva\")`"]);
+        assert_eq!(check("(def twice (x) (+ x x)) (twice 2)"), (Type::Any, vec![]));
+    }
+
+    #[test]
+    fn test_unions_from_builtins() {
+        assert_eq!(check("(+ (to-number \"4\") 1)").1, vec!["`+` expects two numbers or two strings, found (or number error) and number in `(+ (to-number \"4\") 1)`"]);
+        assert_eq!(check("(var (n : (or number error)) (to-number \"4\"))").1, Vec::<String>::new());
+        assert_eq!(check("(if ((> 1 0) 1 \"one\"))").0, Type::Union(vec![Type::Number, Type::String]));
+        assert_eq!(check("(var (x : (or number null)) null) (?? x \"none\")").0, Type::Union(vec![Type::Number, Type::String]));
+        assert_eq!(check("(begin) (while ((> 1 0) 1))").0, Type::Union(vec![Type::Null, Type::Number]));
+    }
+
+    #[test]
+    fn test_conditional_forms() {
+        assert_eq!(check("(if (> 1 0) 1)").0, Type::Union(vec![Type::Number, Type::Null]));
+        assert_eq!(check("(when (> 1 0) \"yes\")").0, Type::Union(vec![Type::String, Type::Null]));
+        assert_eq!(check("(cond ((> 1 0) 1) (else \"one\"))").0, Type::Union(vec![Type::Number, Type::String]));
+        assert_eq!(check("(if 1 2 3)").1, vec!["`if` condition expects (or bool null), found number in `(if 1 2 3)`"]);
+    }
+
+    #[test]
+    fn test_let_forms() {
+        assert_eq!(check("(let ((x 1) (y \"a\")) (+ y y))").0, Type::String);
+        assert_eq!(check("(let* ((x 1) (y x)) (+ y \"a\"))").1, vec!["`+` expects two numbers or two strings, found number and string in `(+ y \"a\")`"]);
+        assert_eq!(check("(let ((x 1)) x) x").1, vec!["undefined variable `x` in `x`"]);
+        assert_eq!(check("(letrec ((f (lambda (n) (g n))) (g (lambda (n) n))) (f 1))").1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!(check("(var (a b . rest) (split \"a,b,c\" \",\")) (+ a b)").1, Vec::<String>::new());
+        assert_eq!(check("(var (x float) 1)").1, vec!["pattern expects list, found number in `(var (x float) 1)`"]);
+        assert_eq!(check("(var (x : float) 1)").1, vec!["unknown type `float` in `(var (x : float) 1)`"]);
+        assert_eq!(check("(match 1 (0 \"zero\") (n when (> n 9) \"big\") (_ 1))").0, Type::Union(vec![Type::String, Type::Number]));
+        assert_eq!(check("(match 1 ((map \"k\" v) v)) v").1, vec!["undefined variable `v` in `v`"]);
+    }
+
+    #[test]
+    fn test_undefined_names() {
+        assert_eq!(check("(set missing 1)").1, vec!["assignment to undeclared variable `missing` in `(set missing 1)`"]);
+        assert_eq!(check("(+ missing 1)").1, vec!["undefined variable `missing` in `missing`"]);
+        assert_eq!(check("(var x missing)").1, vec!["undefined variable `missing` in `missing`"]);
+        // A call is still shown as one.
+        assert_eq!(check("(missing)").1, vec!["undefined variable `missing` in `(missing)`"]);
+    }
+
+    #[test]
+    fn test_bound_names_shadow_built_ins() {
+        assert_eq!(check("(var (upper : number) 5) (+ upper 1)"), (Type::Number, vec![]));
+        assert_eq!(check("(var (now : string) \"noon\") (upper now)"), (Type::String, vec![]));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::eval_error::{ErrorKind, EvalError};
+use crate::string_library::{expect_arity, to_text};
+use crate::EvalDataType;
+
+pub const TYPE_BUILTINS: [&str; 12] = [
+    "to-string", "to-number", "to-bool", "type-of",
+    "number?", "string?", "bool?", "list?", "error?", "function?", "map?", "null?",
+];
+
+pub fn is_type_builtin(name: &str) -> bool {
+    TYPE_BUILTINS.contains(&name)
+}
+
+pub fn call(name: &str, args: &[EvalDataType]) -> EvalDataType {
+    let result = expect_arity(name, args, 1, 1).and_then(|_| match name {
+        "to-string" => Ok(EvalDataType::String(to_text(&args[0]))),
+        "to-number" => to_number(&args[0]),
+        "to-bool" => to_bool(&args[0]),
+        "type-of" => Ok(EvalDataType::String(type_name(&args[0]).to_owned())),
+        "number?" => Ok(EvalDataType::Bool(matches!(args[0], EvalDataType::Number(_)))),
+        "string?" => Ok(EvalDataType::Bool(matches!(args[0], EvalDataType::String(_)))),
+        "bool?" => Ok(EvalDataType::Bool(matches!(args[0], EvalDataType::Bool(_)))),
+        "list?" => Ok(EvalDataType::Bool(matches!(args[0], EvalDataType::List(_)))),
+        "error?" => Ok(EvalDataType::Bool(matches!(args[0], EvalDataType::Error(_)))),
+        "function?" => Ok(EvalDataType::Bool(matches!(args[0], EvalDataType::Function(_)))),
+        "map?" => Ok(EvalDataType::Bool(matches!(args[0], EvalDataType::Map(_)))),
+        "null?" => Ok(EvalDataType::Bool(matches!(args[0], EvalDataType::Null))),
+        _ => Err(EvalError::new(ErrorKind::NotFound, format!("`{}` is not a type built-in", name))),
+    });
+    result.unwrap_or_else(EvalDataType::Error)
+}
+
+pub fn type_name(value: &EvalDataType) -> &'static str {
+    match value {
+        EvalDataType::String(_) => "string",
+        EvalDataType::Number(_) => "number",
+        EvalDataType::Bool(_) => "bool",
+        EvalDataType::List(_) => "list",
+        EvalDataType::Error(_) => "error",
+        EvalDataType::Function(_) => "function",
+        EvalDataType::Map(_) => "map",
+        EvalDataType::Null => "null",
+    }
+}
+
+fn to_number(value: &EvalDataType) -> Result<EvalDataType, EvalError> {
+    match value {
+        EvalDataType::Number(number) => Ok(EvalDataType::Number(*number)),
+        EvalDataType::Bool(value) => Ok(EvalDataType::Number(u128::from(*value))),
+        EvalDataType::String(text) => text.trim().parse::<u128>()
+            .map(EvalDataType::Number)
+            .map_err(|_| EvalError::new(ErrorKind::InvalidFormat, format!("`to-number` cannot read {:?} as a number", text))),
+        other => Err(EvalError::new(ErrorKind::TypeMismatch, format!("`to-number` cannot convert a {}", type_name(other)))),
+    }
+}
+
+fn to_bool(value: &EvalDataType) -> Result<EvalDataType, EvalError> {
+    match value {
+        EvalDataType::Bool(value) => Ok(EvalDataType::Bool(*value)),
+        EvalDataType::Number(number) => Ok(EvalDataType::Bool(*number != 0)),
+        EvalDataType::String(text) => match text.trim() {
+            "true" => Ok(EvalDataType::Bool(true)),
+            "false" => Ok(EvalDataType::Bool(false)),
+            _ => Err(EvalError::new(ErrorKind::InvalidFormat, format!("`to-bool` cannot read {:?} as a bool", text))),
+        },
+        other => Err(EvalError::new(ErrorKind::TypeMismatch, format!("`to-bool` cannot convert a {}", type_name(other)))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+    use crate::eval_error::{ErrorKind, EvalError};
+    use crate::function::{Function, Signature};
+    use crate::parser::parse;
+    use crate::type_library::call;
+    use crate::{get_environment_manager, EvalDataType};
+
+    fn text(value: &str) -> EvalDataType {
+        EvalDataType::String(value.to_owned())
+    }
+
+    fn every_variant() -> Vec<EvalDataType> {
+        vec![
+            text("42"),
+            EvalDataType::Number(42),
+            EvalDataType::Bool(true),
+            EvalDataType::List(vec![EvalDataType::Number(1), text("a")]),
+            EvalDataType::Error(EvalError::new(ErrorKind::NotFound, "missing".to_owned())),
+            EvalDataType::Function(Rc::new(Function {
+                name: "identity".to_owned(),
+                signature: Signature::parse(&parse("(x) x").unwrap()).unwrap(),
+                env: get_environment_manager(),
+            })),
+            EvalDataType::Map(BTreeMap::from([("a".to_owned(), EvalDataType::Number(1))])),
+            EvalDataType::Null,
+        ]
+    }
+
+    #[test]
+    fn test_type_of_every_variant() {
+        let names: Vec<EvalDataType> = every_variant().into_iter().map(|value| call("type-of", &[value])).collect();
+        assert_eq!(names, vec![text("string"), text("number"), text("bool"), text("list"), text("error"), text("function"), text("map"), text("null")]);
+    }
+
+    #[test]
+    fn test_predicates() {
+        for (predicate, matching) in [("string?", 0), ("number?", 1), ("bool?", 2), ("list?", 3), ("error?", 4), ("function?", 5), ("map?", 6), ("null?", 7)] {
+            for (index, value) in every_variant().into_iter().enumerate() {
+                assert_eq!(call(predicate, &[value]), EvalDataType::Bool(index == matching), "{} on variant {}", predicate, index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_string() {
+        assert_eq!(call("to-string", &[EvalDataType::Number(42)]), text("42"));
+        assert_eq!(call("to-string", &[EvalDataType::Bool(false)]), text("false"));
+        assert_eq!(call("to-string", &[text("as is")]), text("as is"));
+        assert_eq!(call("to-string", &[EvalDataType::List(vec![EvalDataType::Number(1), EvalDataType::Number(2)])]), text("(1 2)"));
+    }
+
+    #[test]
+    fn test_to_number() {
+        assert_eq!(call("to-number", &[text("42")]), EvalDataType::Number(42));
+        assert_eq!(call("to-number", &[text(" 7 ")]), EvalDataType::Number(7));
+        assert_eq!(call("to-number", &[EvalDataType::Bool(true)]), EvalDataType::Number(1));
+        match call("to-number", &[text("forty-two")]) {
+            EvalDataType::Error(error) => assert_eq!(error.kind, ErrorKind::InvalidFormat),
+            other => panic!("expected an error, got {:?}", other),
+        }
+        match call("to-number", &[EvalDataType::List(vec![])]) {
+            EvalDataType::Error(error) => assert_eq!(error.kind, ErrorKind::TypeMismatch),
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_to_bool() {
+        assert_eq!(call("to-bool", &[EvalDataType::Number(0)]), EvalDataType::Bool(false));
+        assert_eq!(call("to-bool", &[EvalDataType::Number(3)]), EvalDataType::Bool(true));
+        assert_eq!(call("to-bool", &[text("true")]), EvalDataType::Bool(true));
+        match call("to-bool", &[text("yes")]) {
+            EvalDataType::Error(error) => assert_eq!(error.kind, ErrorKind::InvalidFormat),
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bound_names_shadow_built_ins() {
+        let interpreter = crate::Interpreter::new();
+        assert_eq!(interpreter.eval_str("(var type-of 5) type-of"), Ok(EvalDataType::Number(5)));
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+use crate::{EvalDataType, EvalType};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct FunctionType {
+    pub params: Vec<Type>,
+    pub result: Box<Type>,
+}
+
+/// Static types for the optional annotations; `Any` is what un-annotated code gets.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Type {
+    Any,
+    Number,
+    String,
+    Bool,
+    List,
+    Error,
+    Map,
+    Null,
+    Function(Option<FunctionType>),
+    Union(Vec<Type>),
+}
+
+impl Type {
+    pub fn union(types: Vec<Type>) -> Type {
+        let mut members: Vec<Type> = vec![];
+        for member in types.into_iter().flat_map(|t| match t {
+            Type::Union(members) => members,
+            t => vec![t],
+        }) {
+            if member == Type::Any {
+                return Type::Any;
+            }
+            if !members.contains(&member) {
+                members.push(member);
+            }
+        }
+        match members.len() {
+            1 => members.pop().unwrap(),
+            _ => Type::Union(members),
+        }
+    }
+    pub fn is_assignable_to(&self, target: &Type) -> bool {
+        match (self, target) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Union(members), target) => members.iter().all(|member| member.is_assignable_to(target)),
+            (source, Type::Union(members)) => members.iter().any(|member| source.is_assignable_to(member)),
+            (Type::Function(_), Type::Function(None)) => true,
+            (Type::Function(Some(source)), Type::Function(Some(target))) => {
+                source.params.len() == target.params.len()
+                    && target.params.iter().zip(&source.params).all(|(t, s)| t.is_assignable_to(s))
+                    && source.result.is_assignable_to(&target.result)
+            }
+            (source, target) => source == target,
+        }
+    }
+    pub fn of_value(value: &EvalDataType) -> Type {
+        match value {
+            EvalDataType::String(_) => Type::String,
+            EvalDataType::Number(_) => Type::Number,
+            EvalDataType::Bool(_) => Type::Bool,
+            EvalDataType::List(_) => Type::List,
+            EvalDataType::Error(_) => Type::Error,
+            EvalDataType::Function(_) => Type::Function(None),
+            EvalDataType::Map(_) => Type::Map,
+            EvalDataType::Null => Type::Null,
+        }
+    }
+    pub fn accepts(&self, value: &EvalDataType) -> bool {
+        Type::of_value(value).is_assignable_to(self)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::List => write!(f, "list"),
+            Type::Error => write!(f, "error"),
+            Type::Map => write!(f, "map"),
+            Type::Null => write!(f, "null"),
+            Type::Function(None) => write!(f, "function"),
+            Type::Function(Some(signature)) => {
+                let params: Vec<String> = signature.params.iter().map(Type::to_string).collect();
+                write!(f, "(function ({}) {})", params.join(" "), signature.result)
+            }
+            Type::Union(members) => {
+                let members: Vec<String> = members.iter().map(Type::to_string).collect();
+                write!(f, "(or {})", members.join(" "))
+            }
+        }
+    }
+}
+
+/// Reads an annotation such as `number`, `any` or `(or number string)`.
+pub fn parse_type(exp: &EvalType) -> Result<Type, String> {
+    match exp {
+        EvalType::Value(EvalDataType::String(name)) => match name.as_str() {
+            "any" => Ok(Type::Any),
+            "number" => Ok(Type::Number),
+            "string" => Ok(Type::String),
+            "bool" => Ok(Type::Bool),
+            "list" => Ok(Type::List),
+            "error" => Ok(Type::Error),
+            "map" => Ok(Type::Map),
+            "function" => Ok(Type::Function(None)),
+            other => Err(format!("unknown type `{}`", other)),
+        },
+        // `null` is read as the null literal, which doubles as the name of its type.
+        EvalType::Value(EvalDataType::Null) => Ok(Type::Null),
+        EvalType::Content(content, _) if content.first() == Some(&EvalType::Value(EvalDataType::String("or".to_owned()))) && content.len() > 2 => {
+            Ok(Type::union(content[1..].iter().map(parse_type).collect::<Result<Vec<Type>, String>>()?))
+        }
+        other => Err(format!("invalid type annotation {}", crate::printer::print_inline(other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse;
+    use crate::types::{parse_type, FunctionType, Type};
+    use crate::EvalDataType;
+
+    #[test]
+    fn test_parse_type() {
+        let annotations = parse("number any (or number string) (or bool (or bool list)) float").unwrap();
+        assert_eq!(parse_type(&annotations[0]), Ok(Type::Number));
+        assert_eq!(parse_type(&annotations[1]), Ok(Type::Any));
+        assert_eq!(parse_type(&annotations[2]), Ok(Type::Union(vec![Type::Number, Type::String])));
+        assert_eq!(parse_type(&annotations[3]), Ok(Type::Union(vec![Type::Bool, Type::List])));
+        assert_eq!(parse_type(&annotations[4]), Err("unknown type `float`".to_owned()));
+    }
+
+    #[test]
+    fn test_assignability() {
+        let number_or_string = Type::union(vec![Type::Number, Type::String]);
+        assert!(Type::Number.is_assignable_to(&number_or_string));
+        assert!(!number_or_string.is_assignable_to(&Type::Number));
+        assert!(number_or_string.is_assignable_to(&Type::Any));
+        assert!(Type::Any.is_assignable_to(&Type::Number));
+        assert!(!Type::Bool.is_assignable_to(&Type::Number));
+        let square = Type::Function(Some(FunctionType { params: vec![Type::Number], result: Box::new(Type::Number) }));
+        assert!(square.is_assignable_to(&Type::Function(None)));
+        assert_eq!(Type::union(vec![Type::Number, Type::Any]), Type::Any);
+        assert_eq!(square.to_string(), "(function (number) number)");
+    }
+
+    #[test]
+    fn test_accepts_values() {
+        assert!(Type::Number.accepts(&EvalDataType::Number(1)));
+        assert!(!Type::Number.accepts(&EvalDataType::String("1".to_owned())));
+        assert!(Type::union(vec![Type::Number, Type::Bool]).accepts(&EvalDataType::Bool(true)));
+    }
+}
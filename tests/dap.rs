@@ -0,0 +1,185 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use serde_json::{json, Value};
+
+const PROGRAM: &str = "(def scale (x)
+  (* x 10))
+(var items (json-parse \"[1, 2]\"))
+(var total 0)
+(var i 0)
+(while ((< i 2)
+  (begin ((set total (+ total (scale (get items i))))
+    (set i (+ i 1))))))
+(println \"total\" total)
+total
+";
+
+/// Drives `eva dap` the way an editor would, one request at a time.
+struct Client {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    seq: u64,
+    // Events that arrived while waiting for a response.
+    events: Vec<Value>,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_eva")).arg("dap")
+            .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null())
+            .spawn().unwrap();
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        Client { child, input, output, seq: 0, events: vec![] }
+    }
+    fn read(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            assert!(self.output.read_line(&mut line).unwrap() > 0, "the server closed its output");
+            match line.trim_end() {
+                "" => break,
+                header => length = header.strip_prefix("Content-Length: ").unwrap().parse().unwrap(),
+            }
+        }
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let body = json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments }).to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+        loop {
+            let message = self.read();
+            if message["type"] == "response" {
+                assert_eq!((&message["request_seq"], &message["command"]), (&json!(self.seq), &json!(command)));
+                return message;
+            }
+            self.events.push(message);
+        }
+    }
+    fn event(&mut self, event: &str) -> Value {
+        if let Some(index) = self.events.iter().position(|message| message["event"] == event) {
+            return self.events.remove(index);
+        }
+        loop {
+            let message = self.read();
+            if message["event"] == event {
+                return message;
+            }
+            self.events.push(message);
+        }
+    }
+    fn variables(&mut self, reference: &Value) -> Vec<(String, String)> {
+        let response = self.request("variables", json!({ "variablesReference": reference }));
+        response["body"]["variables"].as_array().unwrap().iter()
+            .map(|variable| (variable["name"].as_str().unwrap().to_owned(), variable["value"].as_str().unwrap().to_owned()))
+            .collect()
+    }
+}
+
+fn launch(client: &mut Client, name: &str, stop_on_entry: bool, breakpoints: &[usize]) -> std::path::PathBuf {
+    launch_program(client, name, PROGRAM, stop_on_entry, breakpoints).0
+}
+
+// Returns the path and whether each breakpoint was verified.
+fn launch_program(client: &mut Client, name: &str, program: &str, stop_on_entry: bool, breakpoints: &[usize]) -> (std::path::PathBuf, Vec<bool>) {
+    let path = std::env::temp_dir().join(format!("eva-dap-{}-{}.eva", name, std::process::id()));
+    std::fs::write(&path, program).unwrap();
+    assert_eq!(client.request("initialize", json!({ "adapterID": "eva" }))["success"], json!(true));
+    client.event("initialized");
+    assert_eq!(client.request("launch", json!({ "program": path, "stopOnEntry": stop_on_entry }))["success"], json!(true));
+    let lines: Vec<Value> = breakpoints.iter().map(|line| json!({ "line": line })).collect();
+    let response = client.request("setBreakpoints", json!({ "source": { "path": path }, "breakpoints": lines }));
+    let verified: Vec<bool> = response["body"]["breakpoints"].as_array().unwrap().iter().map(|breakpoint| breakpoint["verified"].as_bool().unwrap()).collect();
+    assert_eq!(verified.len(), breakpoints.len());
+    assert_eq!(client.request("configurationDone", json!({}))["success"], json!(true));
+    (path, verified)
+}
+
+#[test]
+fn test_breakpoint_inspection() {
+    let mut client = Client::start();
+    let path = launch(&mut client, "inspect", false, &[2]);
+    assert_eq!(client.event("stopped")["body"]["reason"], "breakpoint");
+
+    let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+    let frames = trace["body"]["stackFrames"].as_array().unwrap();
+    let names: Vec<(&str, u64)> = frames.iter().map(|frame| (frame["name"].as_str().unwrap(), frame["line"].as_u64().unwrap())).collect();
+    assert_eq!(names, vec![("scale", 2), ("<main>", 7)]);
+
+    let scopes = client.request("scopes", json!({ "frameId": 0 }));
+    let scopes = scopes["body"]["scopes"].as_array().unwrap().clone();
+    assert_eq!(scopes.iter().map(|scope| scope["name"].as_str().unwrap()).collect::<Vec<&str>>(), vec!["Locals", "Globals"]);
+    assert_eq!(client.variables(&scopes[0]["variablesReference"]), vec![("x".to_owned(), "1".to_owned())]);
+    let globals = client.variables(&scopes[1]["variablesReference"]);
+    assert!(globals.contains(&("total".to_owned(), "0".to_owned())), "{:?}", globals);
+
+    // The caller is inside the loop's block, and lists expand into their items.
+    let caller = client.request("scopes", json!({ "frameId": 1 }));
+    let reference = caller["body"]["scopes"].as_array().unwrap().last().unwrap()["variablesReference"].clone();
+    let items = client.request("variables", json!({ "variablesReference": reference }));
+    let items = items["body"]["variables"].as_array().unwrap().iter().find(|variable| variable["name"] == "items").unwrap().clone();
+    assert_eq!(items["value"], "(1 2)");
+    assert_eq!(client.variables(&items["variablesReference"]), vec![("0".to_owned(), "1".to_owned()), ("1".to_owned(), "2".to_owned())]);
+
+    let evaluated = client.request("evaluate", json!({ "expression": "(* x 7)", "frameId": 0 }));
+    assert_eq!(evaluated["body"]["result"], "7");
+    let failed = client.request("evaluate", json!({ "expression": "(println x)", "frameId": 0 }));
+    assert_eq!(failed["success"], json!(false));
+
+    // Clear the breakpoint and run to the end.
+    client.request("setBreakpoints", json!({ "source": { "path": path }, "breakpoints": [] }));
+    assert_eq!(client.request("continue", json!({ "threadId": 1 }))["success"], json!(true));
+    let output = client.event("output");
+    assert_eq!((&output["body"]["category"], &output["body"]["output"]), (&json!("stdout"), &json!("total 30\n")));
+    assert_eq!(client.event("exited")["body"]["exitCode"], json!(0));
+    client.event("terminated");
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_stepping() {
+    let mut client = Client::start();
+    let path = launch(&mut client, "step", true, &[]);
+    assert_eq!(client.event("stopped")["body"]["reason"], "entry");
+    let line = |client: &mut Client| {
+        let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+        trace["body"]["stackFrames"][0]["line"].as_u64().unwrap()
+    };
+    assert_eq!(line(&mut client), 1);
+    for (command, expected) in [("next", 3), ("next", 4), ("next", 5), ("next", 6), ("stepIn", 6), ("stepIn", 7), ("stepIn", 7)] {
+        client.request(command, json!({ "threadId": 1 }));
+        assert_eq!(client.event("stopped")["body"]["reason"], "step");
+        assert_eq!(line(&mut client), expected, "after {}", command);
+    }
+    // Disconnecting while paused ends the session.
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_breakpoint_on_repeated_form() {
+    let program = "(var i 0)\n(set i (+ i 1))\n\n(set i (+ i 1))\ni\n";
+    let mut client = Client::start();
+    let (path, verified) = launch_program(&mut client, "repeated", program, false, &[3, 4, 5]);
+    // Nothing starts on the blank line or on the bare `i`, so those breakpoints could never be hit.
+    assert_eq!(verified, vec![false, true, false]);
+    assert_eq!(client.event("stopped")["body"]["reason"], "breakpoint");
+    let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(trace["body"]["stackFrames"][0]["line"], json!(4));
+    let scopes = client.request("scopes", json!({ "frameId": 0 }));
+    let globals = client.variables(&scopes["body"]["scopes"][0]["variablesReference"]);
+    assert!(globals.contains(&("i".to_owned(), "1".to_owned())), "{:?}", globals);
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("exited")["body"]["exitCode"], json!(0));
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_file(path).unwrap();
+}